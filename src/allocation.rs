@@ -0,0 +1,605 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// An idle robot offered to an allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct IdleRobot {
+    pub id: usize,
+    pub position: Vec3,
+    /// Heaviest load the robot's type can carry.
+    pub payload: f32,
+    /// Footprint radius of the robot's type.
+    pub radius: f32,
+    /// What is left of the route the robot is driving, such as a reserved way home, in world
+    /// units; strategies add it to the robot's travel cost.
+    pub committed: f32,
+}
+
+impl IdleRobot {
+    /// True if the robot can carry the task's load and is narrow enough to reach it.
+    pub fn can_take(&self, task: &OpenTask) -> bool {
+        task.load <= self.payload && self.radius < task.max_radius
+    }
+}
+
+/// An unassigned, reachable task offered to an allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct OpenTask {
+    pub id: usize,
+    /// Pickup location, where the robot has to drive first.
+    pub position: Vec3,
+    /// Dropoff location for pickup-and-delivery tasks.
+    pub dropoff: Option<Vec3>,
+    /// Payload the load needs.
+    pub load: f32,
+    /// Robots this wide or wider have already failed to find a route to the task.
+    pub max_radius: f32,
+    /// How much nearer the task counts for its priority and deadline, in world units;
+    /// strategies subtract it from a robot's travel cost.
+    pub urgency: f32,
+}
+
+impl OpenTask {
+    /// Where a robot ends up once the task is done.
+    pub fn end(&self) -> Vec3 {
+        self.dropoff.unwrap_or(self.position)
+    }
+}
+
+/// A robot-to-task pairing chosen by an allocator.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Assignment {
+    pub robot_id: usize,
+    pub task_id: usize,
+    /// Simulated seconds until the robot learns of the award; `0.0` for centralized
+    /// allocators that command robots directly.
+    pub delay: f64,
+}
+
+/// A task allocation strategy.
+///
+/// Called whenever at least one robot is idle and at least one task is open. Robots and
+/// tasks arrive sorted by id; each task may appear in at most one returned assignment, and
+/// robots left out simply stay idle until the next call. A robot given several tasks works
+/// through them in the order they are returned. Robots may only be given tasks they
+/// `can_take`.
+pub trait Allocator: Send + Sync {
+    fn allocate(&mut self, robots: &[IdleRobot], tasks: &[OpenTask], now: f64) -> Vec<Assignment>;
+}
+
+/// Gives each idle robot, in id order, the task it can take with the lowest urgency-adjusted
+/// distance that is not yet taken this round. Its committed route counts towards the cost.
+pub struct GreedyAllocator;
+
+impl Allocator for GreedyAllocator {
+    fn allocate(&mut self, robots: &[IdleRobot], tasks: &[OpenTask], _now: f64) -> Vec<Assignment> {
+        let mut taken = vec![false; tasks.len()];
+        let mut assignments = Vec::new();
+
+        for robot in robots {
+            let nearest = tasks
+                .iter()
+                .enumerate()
+                .filter(|(i, task)| !taken[*i] && robot.can_take(task))
+                .min_by(|(_, a), (_, b)| {
+                    let da = robot.committed + robot.position.distance(a.position) - a.urgency;
+                    let db = robot.committed + robot.position.distance(b.position) - b.urgency;
+                    da.total_cmp(&db)
+                });
+            let Some((index, task)) = nearest else {
+                continue;
+            };
+
+            taken[index] = true;
+            assignments.push(Assignment {
+                robot_id: robot.id,
+                task_id: task.id,
+                delay: 0.0,
+            });
+        }
+
+        assignments
+    }
+}
+
+/// Solves the robot-to-task assignment as a min-cost bipartite matching over straight-line
+/// travel distance plus the robot's committed route, less each task's urgency, so robots
+/// that go idle together are matched globally rather than in query order. With a single
+/// idle robot this is the same as nearest-task.
+///
+/// Pairs where the robot cannot take the task get a prohibitive cost and are dropped from
+/// the result if the matching still had to use them.
+pub struct HungarianAllocator;
+
+/// Cost of a robot-task pair the robot cannot take; dwarfs any real travel distance.
+const INELIGIBLE_COST: f64 = 1e9;
+
+impl Allocator for HungarianAllocator {
+    fn allocate(&mut self, robots: &[IdleRobot], tasks: &[OpenTask], now: f64) -> Vec<Assignment> {
+        if robots.len() <= 1 {
+            return GreedyAllocator.allocate(robots, tasks, now);
+        }
+
+        let distance = |robot: &IdleRobot, task: &OpenTask| {
+            if robot.can_take(task) {
+                (robot.committed + robot.position.distance(task.position) - task.urgency) as f64
+            } else {
+                INELIGIBLE_COST
+            }
+        };
+        let eligible = |assignment: &Assignment| {
+            let robot = robots.iter().find(|r| r.id == assignment.robot_id);
+            let task = tasks.iter().find(|t| t.id == assignment.task_id);
+            robot
+                .zip(task)
+                .is_some_and(|(robot, task)| robot.can_take(task))
+        };
+
+        // The solver needs rows <= columns, so put whichever side is smaller on the rows.
+        if robots.len() <= tasks.len() {
+            let cost: Vec<Vec<f64>> = robots
+                .iter()
+                .map(|robot| tasks.iter().map(|task| distance(robot, task)).collect())
+                .collect();
+            solve_assignment(&cost)
+                .into_iter()
+                .enumerate()
+                .map(|(r, t)| Assignment {
+                    robot_id: robots[r].id,
+                    task_id: tasks[t].id,
+                    delay: 0.0,
+                })
+                .filter(eligible)
+                .collect()
+        } else {
+            let cost: Vec<Vec<f64>> = tasks
+                .iter()
+                .map(|task| robots.iter().map(|robot| distance(robot, task)).collect())
+                .collect();
+            let mut assignments: Vec<Assignment> = solve_assignment(&cost)
+                .into_iter()
+                .enumerate()
+                .map(|(t, r)| Assignment {
+                    robot_id: robots[r].id,
+                    task_id: tasks[t].id,
+                    delay: 0.0,
+                })
+                .filter(eligible)
+                .collect();
+            assignments.sort_by_key(|assignment| assignment.robot_id);
+            assignments
+        }
+    }
+}
+
+/// Hungarian (Kuhn–Munkres) algorithm with potentials, O(n²·m).
+///
+/// `cost` is an `n × m` matrix with `n <= m`; returns the column matched to each row such
+/// that the total cost is minimal and no column is used twice.
+fn solve_assignment(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost.first().map_or(0, Vec::len);
+    debug_assert!(
+        n <= m,
+        "solve_assignment needs at least as many columns as rows"
+    );
+
+    // 1-based arrays; column 0 is a virtual column holding the row being inserted.
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; m + 1];
+    let mut row_of_col = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+
+    for row in 1..=n {
+        row_of_col[0] = row;
+        let mut col0 = 0;
+        let mut min_slack = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+
+        // Grow an alternating tree until it reaches a free column.
+        loop {
+            used[col0] = true;
+            let row0 = row_of_col[col0];
+            let mut delta = f64::INFINITY;
+            let mut col1 = 0;
+            for col in 1..=m {
+                if used[col] {
+                    continue;
+                }
+                let reduced = cost[row0 - 1][col - 1] - u[row0] - v[col];
+                if reduced < min_slack[col] {
+                    min_slack[col] = reduced;
+                    way[col] = col0;
+                }
+                if min_slack[col] < delta {
+                    delta = min_slack[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..=m {
+                if used[col] {
+                    u[row_of_col[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_slack[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if row_of_col[col0] == 0 {
+                break;
+            }
+        }
+
+        // Flip the augmenting path back to the root.
+        loop {
+            let col1 = way[col0];
+            row_of_col[col0] = row_of_col[col1];
+            col0 = col1;
+            if col0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut col_of_row = vec![0; n];
+    for col in 1..=m {
+        if row_of_col[col] != 0 {
+            col_of_row[row_of_col[col] - 1] = col - 1;
+        }
+    }
+    col_of_row
+}
+
+/// Parameters of the auction allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct AuctionSettings {
+    /// Bidding rounds per auction; each round a robot can win at most one more task.
+    pub rounds: usize,
+    /// One-way message delay between the auctioneer and a robot, in simulated seconds.
+    pub latency: f64,
+}
+
+impl Default for AuctionSettings {
+    fn default() -> Self {
+        Self {
+            rounds: 2,
+            latency: 0.1,
+        }
+    }
+}
+
+/// Market-based allocation: open tasks are auctioned to the idle robots in rounds.
+///
+/// Each round every robot bids on every unsold task it can take with its own cost estimate,
+/// the distance from where its queue ends to the pickup plus the travel already queued,
+/// starting with the route the robot is still committed to. Bids are settled cheapest first
+/// after subtracting the task's urgency, one task per robot per round, so later rounds let
+/// a robot queue more work. A round is an announcement and a bid message; the award reaches
+/// the winner one further message later, which `allocate_tasks` models as a delayed
+/// `AwardTask` event.
+pub struct AuctionAllocator {
+    pub settings: AuctionSettings,
+}
+
+impl Allocator for AuctionAllocator {
+    fn allocate(&mut self, robots: &[IdleRobot], tasks: &[OpenTask], _now: f64) -> Vec<Assignment> {
+        let mut queue_end: Vec<Vec3> = robots.iter().map(|robot| robot.position).collect();
+        let mut queue_cost: Vec<f32> = robots.iter().map(|robot| robot.committed).collect();
+        let mut sold = vec![false; tasks.len()];
+        let mut assignments = Vec::new();
+
+        for round in 1..=self.settings.rounds {
+            let mut bids: Vec<(f32, usize, usize)> = Vec::new();
+            for (r, end) in queue_end.iter().enumerate() {
+                for (t, task) in tasks.iter().enumerate() {
+                    if !sold[t] && robots[r].can_take(task) {
+                        bids.push((queue_cost[r] + end.distance(task.position), r, t));
+                    }
+                }
+            }
+            if bids.is_empty() {
+                break;
+            }
+            // Ties go to the lower robot id, then the lower task id, as both arrive sorted.
+            let rank = |&(bid, _, t): &(f32, usize, usize)| bid - tasks[t].urgency;
+            bids.sort_by(|a, b| {
+                rank(a)
+                    .total_cmp(&rank(b))
+                    .then(a.1.cmp(&b.1))
+                    .then(a.2.cmp(&b.2))
+            });
+
+            // Announce + bid, then the award message itself.
+            let delay = self.settings.latency * (2 * round + 1) as f64;
+            let mut won = vec![false; robots.len()];
+            for (bid, r, t) in bids {
+                if won[r] || sold[t] {
+                    continue;
+                }
+                won[r] = true;
+                sold[t] = true;
+                // The robot's queue now ends wherever this task's load is delivered.
+                queue_end[r] = tasks[t].end();
+                queue_cost[r] = bid + tasks[t].position.distance(tasks[t].end());
+                assignments.push(Assignment {
+                    robot_id: robots[r].id,
+                    task_id: tasks[t].id,
+                    delay,
+                });
+            }
+        }
+
+        assignments
+    }
+}
+
+/// The selectable allocation strategies.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocatorKind {
+    Greedy,
+    Hungarian,
+    Auction,
+}
+
+impl AllocatorKind {
+    /// Every strategy, in UI cycling order.
+    pub const ALL: [AllocatorKind; 3] = [
+        AllocatorKind::Greedy,
+        AllocatorKind::Hungarian,
+        AllocatorKind::Auction,
+    ];
+
+    /// Human-readable name shown in the UI and reports.
+    pub fn label(self) -> &'static str {
+        match self {
+            AllocatorKind::Greedy => "Greedy",
+            AllocatorKind::Hungarian => "Hungarian",
+            AllocatorKind::Auction => "Auction",
+        }
+    }
+
+    /// Parses a command-line strategy name such as `greedy`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "greedy" => Some(AllocatorKind::Greedy),
+            "hungarian" | "optimal" => Some(AllocatorKind::Hungarian),
+            "auction" | "market" => Some(AllocatorKind::Auction),
+            _ => None,
+        }
+    }
+
+    /// The strategy after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn build(self, auction: AuctionSettings) -> Box<dyn Allocator> {
+        match self {
+            AllocatorKind::Greedy => Box::new(GreedyAllocator),
+            AllocatorKind::Hungarian => Box::new(HungarianAllocator),
+            AllocatorKind::Auction => Box::new(AuctionAllocator { settings: auction }),
+        }
+    }
+}
+
+/// How strongly task priority and deadlines pull allocation away from pure travel distance.
+#[derive(Clone, Copy, Debug)]
+pub struct TaskWeights {
+    /// World units of extra travel a robot accepts per priority level.
+    pub priority: f32,
+    /// World units of extra travel a robot accepts per second a deadline is closer than
+    /// `DEADLINE_HORIZON`.
+    pub deadline: f32,
+}
+
+/// Deadlines further away than this do not affect allocation, in simulated seconds.
+pub const DEADLINE_HORIZON: f64 = 30.0;
+
+impl Default for TaskWeights {
+    fn default() -> Self {
+        Self {
+            priority: 5.0,
+            deadline: 0.5,
+        }
+    }
+}
+
+impl TaskWeights {
+    /// Urgency of a task at sim time `now`; overdue tasks count as due right now.
+    pub fn urgency(&self, priority: u32, due: Option<f64>, now: f64) -> f32 {
+        let closeness = due.map_or(0.0, |due| {
+            (DEADLINE_HORIZON - (due - now)).clamp(0.0, DEADLINE_HORIZON)
+        });
+        self.priority * priority as f32 + self.deadline * closeness as f32
+    }
+}
+
+#[derive(Resource)]
+/// The allocation strategy `allocate_tasks` currently delegates to.
+pub struct ActiveAllocator {
+    pub kind: AllocatorKind,
+    /// Auction parameters, kept across strategy switches.
+    pub auction: AuctionSettings,
+    /// Priority and deadline weighting, kept across strategy switches.
+    pub weights: TaskWeights,
+    pub allocator: Box<dyn Allocator>,
+}
+
+impl ActiveAllocator {
+    /// Creates a fresh allocator of the given kind.
+    pub fn new(kind: AllocatorKind, auction: AuctionSettings, weights: TaskWeights) -> Self {
+        Self {
+            kind,
+            auction,
+            weights,
+            allocator: kind.build(auction),
+        }
+    }
+
+    /// Switches to a fresh allocator of another kind, keeping its parameters.
+    pub fn select(&mut self, kind: AllocatorKind) {
+        *self = Self::new(kind, self.auction, self.weights);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::SimRng;
+
+    fn robot(id: usize, x: f32, payload: f32) -> IdleRobot {
+        IdleRobot {
+            id,
+            position: Vec3::new(x, 0.0, 0.0),
+            payload,
+            radius: 0.3,
+            committed: 0.0,
+        }
+    }
+
+    fn task(id: usize, x: f32, load: f32) -> OpenTask {
+        OpenTask {
+            id,
+            position: Vec3::new(x, 0.0, 0.0),
+            dropoff: None,
+            load,
+            max_radius: f32::INFINITY,
+            urgency: 0.0,
+        }
+    }
+
+    /// Cheapest total over every way of giving each row its own column, by trying them all.
+    fn brute_force(cost: &[Vec<f64>], row: usize, used: &mut Vec<bool>) -> f64 {
+        if row == cost.len() {
+            return 0.0;
+        }
+        let mut best = f64::INFINITY;
+        for col in 0..used.len() {
+            if !used[col] {
+                used[col] = true;
+                best = best.min(cost[row][col] + brute_force(cost, row + 1, used));
+                used[col] = false;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn assignment_matches_brute_force() {
+        let mut rng = SimRng::new(9);
+        for (rows, cols) in [
+            (1, 1),
+            (2, 2),
+            (3, 3),
+            (4, 4),
+            (5, 5),
+            (2, 4),
+            (3, 5),
+            (1, 6),
+        ] {
+            for _ in 0..50 {
+                let cost: Vec<Vec<f64>> = (0..rows)
+                    .map(|_| {
+                        (0..cols)
+                            .map(|_| {
+                                if rng.next_f32() < 0.2 {
+                                    INELIGIBLE_COST
+                                } else {
+                                    rng.range(-5.0, 20.0).round() as f64
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let cols_used = solve_assignment(&cost);
+                assert_eq!(cols_used.len(), rows);
+                let mut distinct = cols_used.clone();
+                distinct.sort_unstable();
+                distinct.dedup();
+                assert_eq!(distinct.len(), rows, "column reused in {cols_used:?}");
+
+                let total: f64 = cols_used.iter().enumerate().map(|(r, &c)| cost[r][c]).sum();
+                let best = brute_force(&cost, 0, &mut vec![false; cols]);
+                assert!(
+                    (total - best).abs() < 1e-6,
+                    "{total} vs {best} for {cost:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hungarian_beats_greedy_order() {
+        // Greedy sends robot 0 to the task at 1 and robot 1 the long way round to -4.
+        let robots = [robot(0, 0.0, 1.0), robot(1, 2.0, 1.0)];
+        let tasks = [task(0, -4.0, 0.5), task(1, 1.0, 0.5)];
+        let assignments = HungarianAllocator.allocate(&robots, &tasks, 0.0);
+        let pairs: Vec<(usize, usize)> = assignments
+            .iter()
+            .map(|a| (a.robot_id, a.task_id))
+            .collect();
+        assert_eq!(pairs, [(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn prohibitive_pairs_are_dropped() {
+        // Nobody can lift task 0, so the matching has to spend a row on it and that pair goes.
+        let robots = [robot(0, 0.0, 1.0), robot(1, 5.0, 1.0)];
+        let tasks = [task(0, 4.0, 3.0), task(1, 6.0, 0.5)];
+        let assignments = HungarianAllocator.allocate(&robots, &tasks, 0.0);
+        assert_eq!(
+            assignments,
+            [Assignment {
+                robot_id: 1,
+                task_id: 1,
+                delay: 0.0
+            }]
+        );
+
+        // With more robots than tasks the matrix is transposed; the result is the same.
+        let robots = [robot(0, 0.0, 1.0), robot(1, 5.0, 1.0), robot(2, 9.0, 1.0)];
+        let assignments = HungarianAllocator.allocate(&robots, &tasks, 0.0);
+        let pairs: Vec<(usize, usize)> = assignments
+            .iter()
+            .map(|a| (a.robot_id, a.task_id))
+            .collect();
+        assert_eq!(pairs, [(1, 1)]);
+    }
+
+    #[test]
+    fn auction_settles_cheapest_bids_one_task_per_robot_per_round() {
+        let robots = [robot(0, 0.0, 1.0), robot(1, 10.0, 1.0)];
+        let tasks = [
+            task(0, 1.0, 0.5),
+            task(1, 2.0, 0.5),
+            task(2, 9.0, 0.5),
+            task(3, 20.0, 0.5),
+        ];
+        let mut auction = AuctionAllocator {
+            settings: AuctionSettings {
+                rounds: 2,
+                latency: 0.1,
+            },
+        };
+        let won: Vec<(usize, usize, f64)> = auction
+            .allocate(&robots, &tasks, 0.0)
+            .iter()
+            .map(|a| (a.robot_id, a.task_id, (a.delay * 10.0).round() / 10.0))
+            .collect();
+        // Round 1 (award after 0.3 s): robot 0 bids lowest on tasks 0 and 1 but wins only
+        // task 0; robot 1 wins task 2. Round 2 (0.5 s): robots bid from where their queues
+        // end; robot 0 outbids robot 1 for task 1, leaving robot 1 task 3.
+        assert_eq!(won, [(0, 0, 0.3), (1, 2, 0.3), (0, 1, 0.5), (1, 3, 0.5)]);
+
+        // With a single round the remaining tasks stay open for the next call.
+        auction.settings.rounds = 1;
+        let won: Vec<(usize, usize)> = auction
+            .allocate(&robots, &tasks, 0.0)
+            .iter()
+            .map(|a| (a.robot_id, a.task_id))
+            .collect();
+        assert_eq!(won, [(0, 0), (1, 2)]);
+    }
+}
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+
+use crate::map::Obstacle;
+use crate::model::{Event, EventType, ScenarioConfig, SimRng, Simulation, TaskSpec};
+
+/// Gives up on a random pickup after this many samples land on obstacles.
+const MAX_PLACEMENT_ATTEMPTS: usize = 100;
+
+/// How randomly generated tasks are laid out.
+#[derive(Clone, Debug)]
+pub struct TaskTemplate {
+    /// Rectangle pickups are drawn from, as `(min, max)` floor corners; `None` uses the arena
+    /// minus a one-unit border.
+    pub area: Option<(Vec2, Vec2)>,
+    /// Dropoffs picked uniformly per task; empty for point tasks.
+    pub dropoffs: Vec<Vec3>,
+    /// Simulated seconds spent loading at the pickup.
+    pub pickup_service: f64,
+    /// Simulated seconds spent unloading at the dropoff.
+    pub dropoff_service: f64,
+    /// Payload every generated task needs.
+    pub load: f32,
+    /// Priority of every generated task.
+    pub priority: u32,
+    /// Seconds after release each generated task is due, if it has a deadline.
+    pub due_in: Option<f64>,
+}
+
+/// A source of tasks released while the simulation runs.
+#[derive(Clone, Debug)]
+pub enum ArrivalProcess {
+    /// Memoryless arrivals: exponential gaps averaging `1 / rate` seconds from `start`
+    /// until `end`, or for the whole run when `end` is `None`.
+    Poisson {
+        rate: f64,
+        start: f64,
+        end: Option<f64>,
+        template: TaskTemplate,
+    },
+    /// `count` tasks released together at each of `times`.
+    Burst {
+        times: Vec<f64>,
+        count: usize,
+        template: TaskTemplate,
+    },
+    /// Fixed `(release time, task)` orders, e.g. replayed from a recorded order stream.
+    Orders(Vec<(f64, TaskSpec)>),
+}
+
+#[derive(Resource)]
+/// Runtime state of the active scenario's arrival processes.
+pub struct TaskArrivals {
+    processes: Vec<ArrivalProcess>,
+    rng: SimRng,
+    /// Id given to the next released task; continues after the scenario's initial tasks.
+    next_task_id: usize,
+    arena_size: f32,
+    obstacles: Vec<Obstacle>,
+    /// `TaskArrival` events currently in the queue.
+    scheduled: usize,
+}
+
+impl TaskArrivals {
+    /// Prepares the config's arrival processes; nothing is scheduled until `start`.
+    pub fn from_config(config: &ScenarioConfig) -> Self {
+        Self {
+            processes: config.arrivals.clone(),
+            // Separate stream from the layout so adding arrivals does not move existing tasks.
+            rng: SimRng::new(config.seed ^ 0xA55A_0F0F_F0F0_5AA5),
+            next_task_id: config.tasks.len(),
+            arena_size: config.arena_size,
+            obstacles: config.obstacles.clone(),
+            scheduled: 0,
+        }
+    }
+
+    /// Schedules the first arrival of every process.
+    pub fn start(&mut self, sim: &mut Simulation) {
+        for process in 0..self.processes.len() {
+            match &self.processes[process] {
+                ArrivalProcess::Poisson { start, end, .. } => {
+                    let (start, end) = (*start, *end);
+                    let first = start + self.poisson_gap(process);
+                    if end.is_none_or(|end| first <= end) {
+                        self.schedule(sim, first, process, 0);
+                    }
+                }
+                ArrivalProcess::Burst { times, .. } => {
+                    for (index, time) in times.clone().into_iter().enumerate() {
+                        self.schedule(sim, time, process, index);
+                    }
+                }
+                ArrivalProcess::Orders(orders) => {
+                    let times: Vec<f64> = orders.iter().map(|(time, _)| *time).collect();
+                    for (index, time) in times.into_iter().enumerate() {
+                        self.schedule(sim, time, process, index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles a due `TaskArrival` event: returns the tasks to spawn, with their ids, and
+    /// schedules the process's next arrival if it has one.
+    pub fn arrive(
+        &mut self,
+        sim: &mut Simulation,
+        now: f64,
+        process: usize,
+        index: usize,
+    ) -> Vec<(usize, TaskSpec)> {
+        self.scheduled = self.scheduled.saturating_sub(1);
+        let specs = match self.processes[process].clone() {
+            ArrivalProcess::Poisson { end, template, .. } => {
+                let next = now + self.poisson_gap(process);
+                if end.is_none_or(|end| next <= end) {
+                    self.schedule(sim, next, process, index + 1);
+                }
+                self.generate(&template, now).into_iter().collect()
+            }
+            ArrivalProcess::Burst {
+                count, template, ..
+            } => (0..count)
+                .filter_map(|_| self.generate(&template, now))
+                .collect(),
+            ArrivalProcess::Orders(orders) => vec![orders[index].1.clone()],
+        };
+
+        specs
+            .into_iter()
+            .map(|spec| {
+                let id = self.next_task_id;
+                self.next_task_id += 1;
+                (id, spec)
+            })
+            .collect()
+    }
+
+    /// True once no process will release any more tasks.
+    pub fn is_exhausted(&self) -> bool {
+        self.scheduled == 0
+    }
+
+    fn schedule(&mut self, sim: &mut Simulation, timestamp: f64, process: usize, index: usize) {
+        let event_type = EventType::TaskArrival { process, index };
+        if sim.schedule(Event {
+            timestamp,
+            event_type,
+        }) {
+            self.scheduled += 1;
+        }
+    }
+
+    /// Exponentially distributed gap for a Poisson process.
+    fn poisson_gap(&mut self, process: usize) -> f64 {
+        let ArrivalProcess::Poisson { rate, .. } = self.processes[process] else {
+            return 0.0;
+        };
+        -(1.0 - self.rng.next_f64()).ln() / rate
+    }
+
+    /// Draws a task released at `now` from a template, or `None` if no free pickup was found.
+    fn generate(&mut self, template: &TaskTemplate, now: f64) -> Option<TaskSpec> {
+        let half = self.arena_size / 2.0 - 1.0;
+        let (min, max) = template
+            .area
+            .unwrap_or((Vec2::splat(-half), Vec2::splat(half)));
+
+        let pickup = (0..MAX_PLACEMENT_ATTEMPTS).find_map(|_| {
+            let candidate = Vec2::new(self.rng.range(min.x, max.x), self.rng.range(min.y, max.y));
+            let blocked = self.obstacles.iter().any(|o| o.contains(candidate, 0.5));
+            (!blocked).then_some(candidate)
+        })?;
+
+        let dropoff = if template.dropoffs.is_empty() {
+            None
+        } else {
+            let pick = (self.rng.next_u64() % template.dropoffs.len() as u64) as usize;
+            Some(template.dropoffs[pick])
+        };
+
+        Some(TaskSpec {
+            position: Vec3::new(pickup.x, 0.25, pickup.y),
+            dropoff,
+            pickup_service: template.pickup_service,
+            dropoff_service: template.dropoff_service,
+            load: template.load,
+            priority: template.priority,
+            due: template.due_in.map(|due_in| now + due_in),
+            requires: Vec::new(),
+        })
+    }
+}
@@ -60,7 +60,7 @@ pub fn camera_movement(
             let yaw = Quat::from_rotation_y(-ev.delta.x * settings.sensitivity * 0.01);
             let pitch = Quat::from_rotation_x(-ev.delta.y * settings.sensitivity * 0.01);
             transform.rotation = yaw * transform.rotation;
-            transform.rotation = transform.rotation * pitch;
+            transform.rotation *= pitch;
         }
     }
 }
@@ -0,0 +1,209 @@
+use std::path::PathBuf;
+
+use crate::allocation::{AllocatorKind, AuctionSettings, TaskWeights};
+use crate::model::{DEFAULT_SEED, DEFAULT_SIM_STEP};
+use crate::reservation::{DEFAULT_ECBS_WEIGHT, PlannerKind};
+use crate::traffic::DeadlockPolicy;
+
+/// Usage text printed for `--help` and on argument errors.
+pub const USAGE: &str = "\
+Usage: fleetsim [OPTIONS]
+
+Options:
+  --headless             Run without a window and print a summary report
+  --scenario <NAME>      Scenario to run: small, warehouse, stress-test, benchmark[-<ROBOTS>],
+                         or the name of a loaded scenario file (repeatable)
+  --scenario-file <PATH> Load a .toml/.json scenario file in addition to ./scenarios (repeatable)
+  --allocator <NAME>     Task allocation strategy: greedy, hungarian, auction [default: greedy]
+  --auction-rounds <N>   Auction: bidding rounds per auction [default: 2]
+  --auction-latency <SECS>
+                         Auction: one-way message delay in simulated seconds [default: 0.1]
+  --priority-weight <UNITS>
+                         Extra travel distance accepted per task priority level [default: 5]
+  --deadline-weight <UNITS>
+                         Extra travel distance accepted per second a deadline is closer than
+                         30 s [default: 0.5]
+  --planner <NAME>       Route planning: independent, reservation, cbs, ecbs
+                         [default: independent]
+  --ecbs-weight <W>      ECBS: suboptimality bound, at least 1 [default: 1.5]
+  --deadlock-policy <NAME>
+                         Independent routes: robots yield to each other and circular waits
+                         are broken by back-off, replan or abort [default: robots never yield]
+  --compare-allocators   Headless: run each scenario with every allocator and compare them
+  --benchmark            Headless: time conflict detection on 500, 1000 and 2000 robot fleets
+  --check-determinism    Headless: run each scenario twice on four seeds and compare the
+                         executed events
+  --seed <N>             Seed for randomised scenario layouts [default: 42]
+  --time-limit <SECS>    Headless: stop a run after this much simulated time [default: 600]
+  --step <SECS>          Simulated seconds advanced per simulation tick [default: 0.05]
+  --event-driven         Headless: jump the clock from event to event instead of ticking
+  -h, --help             Print this help";
+
+/// Parsed command-line options.
+pub struct CliArgs {
+    /// Run the batch runner instead of opening a window.
+    pub headless: bool,
+    /// Scenario names requested with `--scenario`, in order; resolved once files are loaded.
+    pub scenarios: Vec<String>,
+    /// Extra scenario files requested with `--scenario-file`.
+    pub scenario_files: Vec<PathBuf>,
+    /// Allocation strategy; scenario files may override it when loaded.
+    pub allocator: AllocatorKind,
+    /// Rounds and message latency for the auction allocator.
+    pub auction: AuctionSettings,
+    /// How much allocators favour high-priority and soon-due tasks.
+    pub weights: TaskWeights,
+    /// Route planner; scenario files may override it when loaded.
+    pub planner: PlannerKind,
+    /// Suboptimality bound for the ECBS planner.
+    pub ecbs_weight: f32,
+    /// Deadlock resolution for yielding robots; `None` keeps robots from yielding.
+    pub deadlock_policy: Option<DeadlockPolicy>,
+    /// Run every allocator on each scenario and print a comparison (implies `--headless`).
+    pub compare_allocators: bool,
+    /// Time conflict detection on the benchmark fleets instead of running scenarios.
+    pub benchmark: bool,
+    /// Run each scenario twice per seed and compare the event traces instead of reporting.
+    pub check_determinism: bool,
+    /// Seed for randomised scenario layouts.
+    pub seed: u64,
+    /// Simulated-time cutoff for each headless run, in seconds.
+    pub time_limit: f64,
+    /// Fixed simulated time advanced per simulation tick, in seconds.
+    pub step: f64,
+    /// Jump the clock from event to event instead of ticking (implies `--headless`).
+    pub event_driven: bool,
+    /// True when `--help` was requested.
+    pub help: bool,
+}
+
+impl CliArgs {
+    /// Parses arguments (excluding the program name).
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self {
+            headless: false,
+            scenarios: Vec::new(),
+            scenario_files: Vec::new(),
+            allocator: AllocatorKind::Greedy,
+            auction: AuctionSettings::default(),
+            weights: TaskWeights::default(),
+            planner: PlannerKind::Independent,
+            ecbs_weight: DEFAULT_ECBS_WEIGHT,
+            deadlock_policy: None,
+            compare_allocators: false,
+            benchmark: false,
+            check_determinism: false,
+            seed: DEFAULT_SEED,
+            time_limit: 600.0,
+            step: DEFAULT_SIM_STEP,
+            event_driven: false,
+            help: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => parsed.headless = true,
+                "--scenario" => parsed.scenarios.push(next_value(&mut args, &arg)?),
+                "--scenario-file" => {
+                    parsed
+                        .scenario_files
+                        .push(PathBuf::from(next_value(&mut args, &arg)?));
+                }
+                "--allocator" => {
+                    let name = next_value(&mut args, &arg)?;
+                    parsed.allocator = AllocatorKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown allocator `{name}`"))?;
+                }
+                "--auction-rounds" => {
+                    let raw = next_value(&mut args, &arg)?;
+                    parsed.auction.rounds = match raw.parse::<usize>() {
+                        Ok(rounds) if rounds > 0 => rounds,
+                        _ => {
+                            return Err(format!("`{arg}` expects a positive integer, got `{raw}`"));
+                        }
+                    };
+                }
+                "--auction-latency" => {
+                    let raw = next_value(&mut args, &arg)?;
+                    parsed.auction.latency = match raw.parse::<f64>() {
+                        Ok(latency) if latency.is_finite() && latency >= 0.0 => latency,
+                        _ => {
+                            return Err(format!(
+                                "`{arg}` expects a non-negative number, got `{raw}`"
+                            ));
+                        }
+                    };
+                }
+                "--priority-weight" => {
+                    parsed.weights.priority = parse_non_negative(&mut args, &arg)?;
+                }
+                "--deadline-weight" => {
+                    parsed.weights.deadline = parse_non_negative(&mut args, &arg)?;
+                }
+                "--planner" => {
+                    let name = next_value(&mut args, &arg)?;
+                    parsed.planner = PlannerKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown planner `{name}`"))?;
+                }
+                "--ecbs-weight" => {
+                    let raw = next_value(&mut args, &arg)?;
+                    parsed.ecbs_weight = match raw.parse::<f32>() {
+                        Ok(weight) if weight.is_finite() && weight >= 1.0 => weight,
+                        _ => {
+                            return Err(format!(
+                                "`{arg}` expects a number of at least 1, got `{raw}`"
+                            ));
+                        }
+                    };
+                }
+                "--deadlock-policy" => {
+                    let name = next_value(&mut args, &arg)?;
+                    parsed.deadlock_policy = Some(
+                        DeadlockPolicy::from_name(&name)
+                            .ok_or_else(|| format!("unknown deadlock policy `{name}`"))?,
+                    );
+                }
+                "--compare-allocators" => parsed.compare_allocators = true,
+                "--benchmark" => parsed.benchmark = true,
+                "--check-determinism" => parsed.check_determinism = true,
+                "--seed" => {
+                    let raw = next_value(&mut args, &arg)?;
+                    parsed.seed = raw.parse().map_err(|_| {
+                        format!("`--seed` expects an unsigned integer, got `{raw}`")
+                    })?;
+                }
+                "--time-limit" => parsed.time_limit = parse_positive(&mut args, &arg)?,
+                "--step" => parsed.step = parse_positive(&mut args, &arg)?,
+                "--event-driven" => parsed.event_driven = true,
+                "-h" | "--help" => parsed.help = true,
+                other => return Err(format!("unexpected argument `{other}`")),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("`{flag}` expects a value"))
+}
+
+fn parse_non_negative(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<f32, String> {
+    let raw = next_value(args, flag)?;
+    match raw.parse::<f32>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        _ => Err(format!(
+            "`{flag}` expects a non-negative number, got `{raw}`"
+        )),
+    }
+}
+
+fn parse_positive(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<f64, String> {
+    let raw = next_value(args, flag)?;
+    match raw.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("`{flag}` expects a positive number, got `{raw}`")),
+    }
+}
@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::model::RobotMotion;
+
+/// A predicted meeting of two robots' footprints.
+#[derive(Clone, Copy, Debug)]
+pub struct PredictedConflict {
+    /// Sim time at which the footprints first touch.
+    pub time: f64,
+    /// Point midway between the two robots at that time.
+    pub position: Vec3,
+}
+
+#[derive(Resource, Default)]
+/// Conflicts predicted from the robots' timed routes, and those that came to pass.
+pub struct ConflictForecast {
+    /// Next predicted conflict per robot pair `(lower id, higher id)`; replaced whenever
+    /// either robot's route changes.
+    pub pending: HashMap<(usize, usize), PredictedConflict>,
+    /// Conflicts whose scheduled event fired while the prediction still held, as
+    /// `(robot id, robot id, conflict)`.
+    pub occurred: Vec<(usize, usize, PredictedConflict)>,
+}
+
+impl ConflictForecast {
+    /// Drops the predictions involving any of `robot_ids`, returning the pairs they were for.
+    pub fn forget(&mut self, robot_ids: &HashSet<usize>) -> HashSet<(usize, usize)> {
+        let mut dropped = HashSet::new();
+        self.pending.retain(|&(a, b), _| {
+            let stale = robot_ids.contains(&a) || robot_ids.contains(&b);
+            if stale {
+                dropped.insert((a, b));
+            }
+            !stale
+        });
+        dropped
+    }
+
+    /// Records the conflict scheduled for `robots` at `time` and `position` if it is still
+    /// the current prediction for that pair.
+    pub fn confirm(&mut self, robots: (usize, usize), time: f64, position: Vec3) {
+        if self
+            .pending
+            .get(&robots)
+            .is_some_and(|conflict| conflict.time == time)
+        {
+            self.pending.remove(&robots);
+            self.occurred
+                .push((robots.0, robots.1, PredictedConflict { time, position }));
+        }
+    }
+}
+
+/// Where a robot is over time: its timed route, or `rest` while it has none.
+#[derive(Clone, Copy)]
+pub struct Trajectory<'a> {
+    /// Current route of the robot.
+    pub motion: &'a RobotMotion,
+    /// Position the robot stands at without a route.
+    pub rest: Vec3,
+}
+
+impl Trajectory<'_> {
+    fn position_at(&self, t: f64) -> Vec3 {
+        self.motion.position_at(t).unwrap_or(self.rest)
+    }
+
+    /// Floor area the robot can still cover from `from` on, widened by `radius`.
+    pub fn bounds(&self, from: f64, radius: f32) -> Rect {
+        let start = self.position_at(from).xz();
+        let (min, max) = self
+            .motion
+            .waypoints
+            .iter()
+            .filter(|&&(t, _)| t > from)
+            .fold((start, start), |(min, max), &(_, point)| {
+                (min.min(point.xz()), max.max(point.xz()))
+            });
+        Rect::from_corners(min - radius, max + radius)
+    }
+
+    fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.motion.waypoints.iter().map(|&(t, _)| t)
+    }
+}
+
+/// The first time at or after `from` that two robots come closer than `reach`, with the
+/// point midway between them, or `None` if their routes keep them apart.
+///
+/// Both trajectories are linear between the union of their waypoint times, so on each such
+/// segment the gap is checked analytically at its closest approach; only segments that get
+/// close enough are solved for the moment the footprints touch. A pair that already
+/// overlaps at `from` reports its next contact after separating.
+pub fn first_contact(
+    a: Trajectory,
+    b: Trajectory,
+    reach: f32,
+    from: f64,
+) -> Option<PredictedConflict> {
+    let mut times: Vec<f64> = a.times().chain(b.times()).filter(|&t| t > from).collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    let reach = reach as f64;
+    let gap = |t: f64| {
+        let offset = b.position_at(t).xz() - a.position_at(t).xz();
+        offset.as_dvec2()
+    };
+    let mut inside = gap(from).length_squared() < reach * reach;
+    let mut t0 = from;
+    for t1 in times {
+        let duration = t1 - t0;
+        let (start, end) = (gap(t0), gap(t1));
+        let velocity = (end - start) / duration;
+        let speed_squared = velocity.length_squared();
+        let segment_start = t0;
+        t0 = t1;
+        if speed_squared < 1e-12 {
+            continue;
+        }
+
+        // Closest approach of the relative motion within the segment.
+        let closest = (-start.dot(velocity) / speed_squared).clamp(0.0, duration);
+        if (start + velocity * closest).length_squared() >= reach * reach {
+            inside = false;
+            continue;
+        }
+
+        // |start + velocity·s| = reach: enter at the lower root, leave at the upper one.
+        let half_b = start.dot(velocity);
+        let c = start.length_squared() - reach * reach;
+        let root = (half_b * half_b - speed_squared * c).max(0.0).sqrt();
+        let enter = (-half_b - root) / speed_squared;
+        let leave = (-half_b + root) / speed_squared;
+        if !inside && enter >= 0.0 {
+            let time = segment_start + enter;
+            let position = a.position_at(time).lerp(b.position_at(time), 0.5);
+            return Some(PredictedConflict { time, position });
+        }
+        inside = leave > duration;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A route through `points`, as `(time, x, z)`.
+    fn motion(points: &[(f64, f32, f32)]) -> RobotMotion {
+        RobotMotion {
+            waypoints: points
+                .iter()
+                .map(|&(t, x, z)| (t, Vec3::new(x, 0.0, z)))
+                .collect(),
+        }
+    }
+
+    fn contact(a: &RobotMotion, b: &RobotMotion, from: f64) -> Option<PredictedConflict> {
+        let trajectory = |motion| Trajectory {
+            motion,
+            rest: Vec3::ZERO,
+        };
+        first_contact(trajectory(a), trajectory(b), 1.0, from)
+    }
+
+    #[test]
+    fn head_on_robots_touch_before_they_meet() {
+        let a = motion(&[(0.0, -5.0, 0.0), (10.0, 5.0, 0.0)]);
+        let b = motion(&[(0.0, 5.0, 0.0), (10.0, -5.0, 0.0)]);
+        // 10 − 2t = 1 apart at t = 4.5, either side of the origin.
+        let conflict = contact(&a, &b, 0.0).expect("a head-on contact");
+        assert!((conflict.time - 4.5).abs() < 1e-6, "{conflict:?}");
+        assert!(
+            conflict.position.distance(Vec3::ZERO) < 1e-4,
+            "{conflict:?}"
+        );
+        // Asking later still finds the same contact, but not once it has begun.
+        let later = contact(&a, &b, 3.0).expect("a head-on contact");
+        assert!((later.time - 4.5).abs() < 1e-6, "{later:?}");
+        assert!(contact(&a, &b, 4.6).is_none());
+    }
+
+    #[test]
+    fn parallel_and_diverging_robots_never_touch() {
+        let a = motion(&[(0.0, -5.0, 0.0), (10.0, 5.0, 0.0)]);
+        let beside = motion(&[(0.0, -5.0, 1.5), (10.0, 5.0, 1.5)]);
+        assert!(contact(&a, &beside, 0.0).is_none());
+        let oncoming = motion(&[(0.0, 5.0, 1.5), (10.0, -5.0, 1.5)]);
+        assert!(contact(&a, &oncoming, 0.0).is_none());
+
+        let away = motion(&[(0.0, -3.0, 0.0), (10.0, -8.0, 0.0)]);
+        let onward = motion(&[(0.0, -1.5, 0.0), (10.0, 4.0, 0.0)]);
+        assert!(contact(&away, &onward, 0.0).is_none());
+    }
+
+    #[test]
+    fn catching_up_counts_as_contact() {
+        let fast = motion(&[(0.0, 0.0, 0.0), (4.0, 8.0, 0.0)]);
+        let slow = motion(&[(0.0, 3.0, 0.0), (4.0, 7.0, 0.0)]);
+        // 3 − t = 1 apart at t = 2.
+        let conflict = contact(&fast, &slow, 0.0).expect("the fast robot catches up");
+        assert!((conflict.time - 2.0).abs() < 1e-6, "{conflict:?}");
+        assert!(conflict.position.distance(Vec3::new(4.5, 0.0, 0.0)) < 1e-4);
+    }
+
+    #[test]
+    fn overlapping_robots_report_their_next_contact() {
+        // A robot standing at the origin without a route, and one starting half a unit away.
+        let parked = RobotMotion::default();
+        let leaving = motion(&[(0.0, 0.5, 0.0), (5.0, 3.0, 0.0)]);
+        assert!(contact(&parked, &leaving, 0.0).is_none());
+
+        // Coming back after separating touches again at x = 1: 3 − (t − 5) = 1, t = 7.
+        let returning = motion(&[(0.0, 0.5, 0.0), (5.0, 3.0, 0.0), (10.0, -2.0, 0.0)]);
+        let conflict = contact(&parked, &returning, 0.0).expect("contact on the way back");
+        assert!((conflict.time - 7.0).abs() < 1e-6, "{conflict:?}");
+        assert!(conflict.position.distance(Vec3::new(0.5, 0.0, 0.0)) < 1e-4);
+    }
+}
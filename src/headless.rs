@@ -0,0 +1,735 @@
+use bevy::diagnostic::FrameCount;
+use bevy::prelude::*;
+use std::time::{Duration, Instant};
+
+use crate::allocation::{ActiveAllocator, AllocatorKind, AuctionSettings, TaskWeights};
+use crate::arrivals::TaskArrivals;
+use crate::conflicts::ConflictForecast;
+use crate::model::{
+    ActiveScenario, BENCHMARK_FLEETS, Battery, CollisionState, Robot, RobotPath, Scenario,
+    ScenarioConfig, ScenarioLibrary, Simulation, SimulationClock, SimulationSeed, Task,
+};
+use crate::reservation::{ActivePlanner, PlannerKind};
+use crate::simulation;
+use crate::spatial::{SpatialIndex, overlaps};
+use crate::traffic::{DeadlockCount, DeadlockPolicy, TrafficControl, YIELD_HOLD};
+
+/// Simulated seconds each `--benchmark` fleet runs for.
+const BENCHMARK_TIME: f64 = 20.0;
+/// Consecutive seeds each scenario is run with by `--check-determinism`.
+const DETERMINISM_SEEDS: u64 = 4;
+
+/// Settings shared by every run in a headless batch.
+pub struct HeadlessOptions {
+    /// Scenarios to run, in order.
+    pub scenarios: Vec<Scenario>,
+    /// Simulated-time cutoff for each run, in seconds.
+    pub time_limit: f64,
+    /// Simulated time advanced per update, in seconds.
+    pub step: f64,
+    /// Jump from event to event instead of advancing by `step` each update.
+    pub event_driven: bool,
+    /// Seed for randomised scenario layouts.
+    pub seed: u64,
+    /// Allocation strategy, unless a scenario file selects its own.
+    pub allocator: AllocatorKind,
+    /// Rounds and message latency for the auction allocator.
+    pub auction: AuctionSettings,
+    /// Priority and deadline weighting for every allocator.
+    pub weights: TaskWeights,
+    /// Route planner, unless a scenario file selects its own.
+    pub planner: PlannerKind,
+    /// Suboptimality bound for the ECBS planner.
+    pub ecbs_weight: f32,
+    /// Deadlock resolution for yielding robots; `None` keeps robots from yielding.
+    pub deadlock_policy: Option<DeadlockPolicy>,
+    /// Run every allocator on each scenario and print a comparison table.
+    pub compare_allocators: bool,
+    /// Presets plus any loaded scenario files.
+    pub library: ScenarioLibrary,
+}
+
+/// Settings for the current headless run.
+#[derive(Resource)]
+struct RunSettings {
+    /// Simulated-time cutoff in seconds.
+    time_limit: f64,
+    /// Keep the requested allocator even if the scenario file selects another.
+    force_allocator: bool,
+    /// The clock jumps from event to event.
+    event_driven: bool,
+}
+
+/// Outcome of one headless run, recorded when it finishes.
+#[derive(Resource, Clone)]
+struct RunSummary {
+    scenario: String,
+    seed: u64,
+    allocator: AllocatorKind,
+    planner: PlannerKind,
+    status: &'static str,
+    sim_time: f64,
+    /// Whether each tick jumped to the next event.
+    event_driven: bool,
+    /// Simulation ticks run, and the wall-clock time they took; filled in by `run_scenario`.
+    ticks: u32,
+    wall_time: Duration,
+    total: usize,
+    completed: usize,
+    unreachable: usize,
+    robots: usize,
+    distance: f32,
+    conflicts: u32,
+    /// Conflicts predicted from the robots' routes that came to pass.
+    swept_conflicts: usize,
+    /// Events dropped by `Simulation::schedule` for a non-finite timestamp.
+    rejected_events: u32,
+    /// Deadlock policy and the number of deadlocks it resolved, when robots yield.
+    deadlocks: Option<(DeadlockPolicy, u32)>,
+    /// Completed tasks per simulated minute.
+    throughput: f64,
+    /// Mean time from release to completion over completed tasks.
+    mean_lead_time: Option<f64>,
+    /// Arrival processes were still releasing tasks when the run stopped.
+    open_ended: bool,
+    /// Charging sessions completed and the lowest charge any robot reached, when the
+    /// scenario models batteries.
+    battery: Option<(u32, f32)>,
+    /// Completion against due times, when any task has one.
+    deadlines: Option<DeadlineReport>,
+}
+
+/// How tasks with a due time fared.
+#[derive(Clone, Copy, Default)]
+struct DeadlineReport {
+    /// Completed by their due time.
+    on_time: usize,
+    /// Completed after their due time.
+    late: usize,
+    /// Not completed and already past their due time when the run stopped.
+    overdue: usize,
+    /// Sum of how long late tasks were completed after their due times, in seconds.
+    tardiness: f64,
+}
+
+impl RunSummary {
+    /// Every task completed; for open-ended arrivals, nothing turned out unreachable.
+    fn succeeded(&self) -> bool {
+        self.rejected_events == 0
+            && (self.completed == self.total || (self.open_ended && self.unreachable == 0))
+    }
+
+    fn print(&self) {
+        println!("scenario: {} (seed {})", self.scenario, self.seed);
+        println!("  status:     {}", self.status);
+        println!("  allocator:  {}", self.allocator.label());
+        println!("  planner:    {}", self.planner.label());
+        println!("  sim time:   {:.2} s", self.sim_time);
+        let clock = if self.event_driven {
+            "event-driven"
+        } else {
+            "fixed step"
+        };
+        println!(
+            "  clock:      {clock}, {} ticks in {:.2} s",
+            self.ticks,
+            self.wall_time.as_secs_f64()
+        );
+        println!(
+            "  tasks:      {}/{} completed, {} unreachable",
+            self.completed, self.total, self.unreachable
+        );
+        match self.mean_lead_time {
+            Some(lead) => println!(
+                "  throughput: {:.2} tasks/min, mean lead time {lead:.2} s",
+                self.throughput
+            ),
+            None => println!("  throughput: {:.2} tasks/min", self.throughput),
+        }
+        println!("  robots:     {}", self.robots);
+        println!("  distance:   {:.2} units", self.distance);
+        println!("  conflicts:  {} robot collision entries", self.conflicts);
+        println!(
+            "  swept:      {} predicted from routes",
+            self.swept_conflicts
+        );
+        if self.rejected_events > 0 {
+            println!(
+                "  rejected:   {} events with a non-finite timestamp",
+                self.rejected_events
+            );
+        }
+        if let Some((policy, count)) = self.deadlocks {
+            println!("  deadlocks:  {count} resolved ({})", policy.label());
+        }
+        if let Some(deadlines) = self.deadlines {
+            println!(
+                "  deadlines:  {} on time, {} late, {} overdue, total tardiness {:.2} s",
+                deadlines.on_time, deadlines.late, deadlines.overdue, deadlines.tardiness
+            );
+        }
+        if let Some((charges, lowest)) = self.battery {
+            println!(
+                "  battery:    {charges} charging sessions, lowest level {:.0}%",
+                lowest * 100.0
+            );
+        }
+    }
+}
+
+/// Runs every requested scenario without a window and prints a report for each.
+///
+/// With `compare_allocators` each scenario is run once per allocator and a table comparing
+/// them against `Greedy` follows the reports. Returns `AppExit::Success` only if every run
+/// completed all of its tasks within the time limit (unreachable tasks count as failures);
+/// runs with open-ended arrivals succeed at the time limit unless a task was unreachable.
+pub fn run_batch(options: &HeadlessOptions) -> AppExit {
+    let allocators: Vec<AllocatorKind> = if options.compare_allocators {
+        AllocatorKind::ALL.to_vec()
+    } else {
+        vec![options.allocator]
+    };
+
+    let mut summaries = Vec::new();
+    for &scenario in &options.scenarios {
+        for &allocator in &allocators {
+            let Some(summary) = run_scenario(scenario, allocator, options) else {
+                eprintln!("error: run ended without a report");
+                return AppExit::error();
+            };
+            summary.print();
+            summaries.push(summary);
+        }
+    }
+
+    if options.compare_allocators {
+        print_comparison(&summaries);
+    }
+
+    if summaries.iter().all(RunSummary::succeeded) {
+        AppExit::Success
+    } else {
+        AppExit::error()
+    }
+}
+
+/// Prints one row per run, with sim time and distance relative to the `Greedy` run of the
+/// same scenario.
+fn print_comparison(summaries: &[RunSummary]) {
+    println!();
+    println!("allocator comparison (relative to Greedy):");
+    println!(
+        "  {:<16} {:<10} {:>11} {:>9} {:>18} {:>18} {:>10} {:>10} {:>6} {:>14}",
+        "scenario",
+        "allocator",
+        "completed",
+        "missed",
+        "sim time (s)",
+        "distance",
+        "tasks/min",
+        "conflicts",
+        "late",
+        "tardiness (s)"
+    );
+    for summary in summaries {
+        let baseline = summaries.iter().find(|other| {
+            other.scenario == summary.scenario && other.allocator == AllocatorKind::Greedy
+        });
+        let relative = |value: f64, base: Option<f64>| match base {
+            Some(base) if summary.allocator != AllocatorKind::Greedy && base > 0.0 => {
+                format!("{value:.2} ({:+.1}%)", (value / base - 1.0) * 100.0)
+            }
+            _ => format!("{value:.2}"),
+        };
+        let deadlines = summary.deadlines.unwrap_or_default();
+        println!(
+            "  {:<16} {:<10} {:>11} {:>9} {:>18} {:>18} {:>10.2} {:>10} {:>6} {:>14.2}",
+            summary.scenario,
+            summary.allocator.label(),
+            format!("{}/{}", summary.completed, summary.total),
+            summary.total - summary.completed,
+            relative(summary.sim_time, baseline.map(|b| b.sim_time)),
+            relative(summary.distance as f64, baseline.map(|b| b.distance as f64)),
+            summary.throughput,
+            summary.conflicts,
+            deadlines.late + deadlines.overdue,
+            deadlines.tardiness,
+        );
+    }
+}
+
+/// Builds a renderer-free app for one scenario and runs it to completion.
+fn run_scenario(
+    scenario: Scenario,
+    allocator: AllocatorKind,
+    options: &HeadlessOptions,
+) -> Option<RunSummary> {
+    let mut app = build_app(
+        scenario,
+        allocator,
+        options,
+        options.seed,
+        options.time_limit,
+    );
+    let start = Instant::now();
+    drive(&mut app);
+    let wall_time = start.elapsed();
+    let world = app.world();
+    let mut summary = world.get_resource::<RunSummary>().cloned()?;
+    // One simulation tick per update.
+    summary.ticks = world.resource::<FrameCount>().0;
+    summary.wall_time = wall_time;
+    Some(summary)
+}
+
+/// Runs every requested scenario twice for each of `DETERMINISM_SEEDS` seeds from
+/// `options.seed` on, recording the executed events, and checks that both runs of a seed
+/// execute exactly the same events in the same order.
+///
+/// Prints one line per scenario and seed, with the first differing event of a run that
+/// diverged; returns `AppExit::Success` only if every pair of runs matched.
+pub fn run_determinism_check(options: &HeadlessOptions) -> AppExit {
+    let Some(last_seed) = options.seed.checked_add(DETERMINISM_SEEDS - 1) else {
+        eprintln!(
+            "error: `--check-determinism` runs {DETERMINISM_SEEDS} consecutive seeds, so \
+             `--seed` must be at most {}",
+            u64::MAX - (DETERMINISM_SEEDS - 1)
+        );
+        return AppExit::error();
+    };
+    println!(
+        "determinism check (two runs per seed, seeds {}..={last_seed}):",
+        options.seed
+    );
+    let mut identical = true;
+    for &scenario in &options.scenarios {
+        for seed in options.seed..=last_seed {
+            let (name, first) = traced_run(scenario, options, seed);
+            let (_, second) = traced_run(scenario, options, seed);
+            let divergence = first
+                .iter()
+                .zip(&second)
+                .position(|(a, b)| a != b)
+                .or((first.len() != second.len()).then(|| first.len().min(second.len())));
+            let Some(at) = divergence else {
+                println!(
+                    "  {name:<16} seed {seed:<6} {:>7} events, identical",
+                    first.len()
+                );
+                continue;
+            };
+            identical = false;
+            let show = |trace: &[String]| trace.get(at).cloned().unwrap_or("end of run".into());
+            println!("  {name:<16} seed {seed:<6} diverged at event {at}:");
+            println!("    first:  {}", show(&first));
+            println!("    second: {}", show(&second));
+        }
+    }
+    if identical {
+        AppExit::Success
+    } else {
+        AppExit::error()
+    }
+}
+
+/// Runs one scenario with `seed`, returning its name and the events it executed.
+fn traced_run(scenario: Scenario, options: &HeadlessOptions, seed: u64) -> (String, Vec<String>) {
+    let mut app = build_app(
+        scenario,
+        options.allocator,
+        options,
+        seed,
+        options.time_limit,
+    );
+    app.world_mut().resource_mut::<Simulation>().record_trace();
+    drive(&mut app);
+    let world = app.world();
+    let name = world
+        .get_resource::<RunSummary>()
+        .map(|summary| summary.scenario.clone());
+    (
+        name.unwrap_or_default(),
+        world.resource::<Simulation>().trace().to_vec(),
+    )
+}
+
+/// Runs the `Benchmark` scenario for each of `BENCHMARK_FLEETS` and prints how the frame
+/// time and the cost of finding overlapping robots grow with the fleet.
+///
+/// Each frame, the overlapping pairs are found both with a fresh `SpatialIndex` and by
+/// comparing every pair of robots; the run fails if the two ever disagree. The frame time
+/// excludes both of those checks.
+pub fn run_benchmark(options: &HeadlessOptions) -> AppExit {
+    println!(
+        "conflict detection benchmark ({BENCHMARK_TIME} s simulated per fleet, seed {}):",
+        options.seed
+    );
+    println!(
+        "  {:>7} {:>7} {:>11} {:>11} {:>15} {:>9} {:>10}",
+        "robots", "frames", "frame (ms)", "grid (µs)", "all pairs (µs)", "speed-up", "mismatches"
+    );
+    let mut agreed = true;
+    for count in BENCHMARK_FLEETS {
+        let scenario = Scenario::Benchmark(count);
+        let mut app = build_app(
+            scenario,
+            options.allocator,
+            options,
+            options.seed,
+            BENCHMARK_TIME,
+        );
+        app.insert_resource(ConflictTimings::default()).add_systems(
+            Update,
+            time_conflict_checks.after(simulation::detect_conflicts),
+        );
+        let start = Instant::now();
+        drive(&mut app);
+        let timings = app.world().resource::<ConflictTimings>();
+        // The reference checks are not part of a frame's real work.
+        let elapsed = start
+            .elapsed()
+            .saturating_sub(timings.grid + timings.all_pairs);
+        let frames = timings.frames.max(1) as f64;
+        let grid = timings.grid.as_secs_f64() * 1e6 / frames;
+        let all_pairs = timings.all_pairs.as_secs_f64() * 1e6 / frames;
+        println!(
+            "  {:>7} {:>7} {:>11.2} {:>11.1} {:>15.1} {:>8.1}x {:>10}",
+            count,
+            timings.frames,
+            elapsed.as_secs_f64() * 1e3 / frames,
+            grid,
+            all_pairs,
+            all_pairs / grid.max(f64::EPSILON),
+            timings.mismatches
+        );
+        agreed &= timings.mismatches == 0;
+    }
+    if agreed {
+        AppExit::Success
+    } else {
+        AppExit::error()
+    }
+}
+
+/// Wall-clock cost of finding the overlapping robots, summed over a benchmark run.
+#[derive(Resource, Default)]
+struct ConflictTimings {
+    frames: u32,
+    /// Rebuilding a `SpatialIndex` and querying it.
+    grid: Duration,
+    /// Comparing every robot with every other.
+    all_pairs: Duration,
+    /// Frames on which the two found a different number of pairs.
+    mismatches: u32,
+}
+
+/// Times the spatial index against the all-pairs check on this frame's robot positions.
+fn time_conflict_checks(index: Res<SpatialIndex>, mut timings: ResMut<ConflictTimings>) {
+    let robots = index.robots().to_vec();
+
+    let start = Instant::now();
+    let mut grid = SpatialIndex::default();
+    grid.rebuild(robots.iter().copied());
+    let found = grid.overlapping_pairs().len();
+    timings.grid += start.elapsed();
+
+    let start = Instant::now();
+    let mut expected = 0;
+    for (i, a) in robots.iter().enumerate() {
+        expected += robots[i + 1..].iter().filter(|b| overlaps(a, b)).count();
+    }
+    timings.all_pairs += start.elapsed();
+
+    timings.frames += 1;
+    if found != expected {
+        timings.mismatches += 1;
+    }
+}
+
+/// Builds a renderer-free app for one scenario and seed, stopping at `time_limit` sim
+/// seconds.
+fn build_app(
+    scenario: Scenario,
+    allocator: AllocatorKind,
+    options: &HeadlessOptions,
+    seed: u64,
+    time_limit: f64,
+) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(Simulation::new())
+        .insert_resource(headless_clock(options, time_limit))
+        .insert_resource(ActiveScenario(scenario))
+        .insert_resource(options.library.clone())
+        .insert_resource(SimulationSeed(seed))
+        .insert_resource(ActiveAllocator::new(
+            allocator,
+            options.auction,
+            options.weights,
+        ))
+        .insert_resource(ActivePlanner::new(options.planner, options.ecbs_weight))
+        .insert_resource(TrafficControl::new(options.deadlock_policy))
+        .insert_resource(RunSettings {
+            time_limit,
+            force_allocator: options.compare_allocators,
+            event_driven: options.event_driven,
+        })
+        .add_systems(Startup, spawn_headless_scenario)
+        .add_systems(
+            Update,
+            (
+                simulation::run_simulation,
+                simulation::update_robot_motion,
+                simulation::update_spatial_index,
+                simulation::yield_to_robots,
+                simulation::resolve_deadlocks,
+                simulation::update_batteries,
+                simulation::allocate_tasks,
+                simulation::detect_conflicts,
+                simulation::predict_conflicts,
+                report_when_finished,
+            )
+                .chain(),
+        );
+    app
+}
+
+/// Updates `app` until it exits.
+///
+/// Updates are driven here rather than through `App::run`, which hands the world to the
+/// runner and would drop the recorded summary.
+fn drive(app: &mut App) {
+    app.finish();
+    app.cleanup();
+    while app.should_exit().is_none() {
+        app.update();
+    }
+}
+
+/// Clock for a headless run: every update is one simulation tick, as fast as the machine
+/// allows, and the clock stops at `time_limit`.
+///
+/// Yielding robots hold for `YIELD_HOLD` before they look again, so an event-driven clock
+/// must not jump further than that while they can yield. Their holds also nudge the robots'
+/// events by ever smaller amounts, which it would chase forever, so it never jumps less than
+/// `step` either.
+fn headless_clock(options: &HeadlessOptions, time_limit: f64) -> SimulationClock {
+    let clock = SimulationClock {
+        step: options.step,
+        event_driven: options.event_driven,
+        until: time_limit,
+        ..SimulationClock::new()
+    };
+    if options.deadlock_policy.is_none() {
+        return clock;
+    }
+    SimulationClock {
+        min_jump: options.step,
+        max_jump: YIELD_HOLD,
+        ..clock
+    }
+}
+
+fn spawn_headless_scenario(
+    mut commands: Commands,
+    active: Res<ActiveScenario>,
+    seed: Res<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
+    settings: Res<RunSettings>,
+) {
+    let mut config = ScenarioConfig::build(active.0, seed.0, &library);
+    if settings.force_allocator {
+        // Comparisons must run every allocator, whatever the scenario file prefers.
+        config.allocator = None;
+    }
+    simulation::spawn_scenario(&mut commands, &config);
+}
+
+/// Records the summary and exits once all tasks, including any still to arrive, are done
+/// or the time limit is hit.
+#[allow(clippy::too_many_arguments)]
+fn report_when_finished(
+    mut commands: Commands,
+    sim: Res<Simulation>,
+    arrivals: Res<TaskArrivals>,
+    settings: Res<RunSettings>,
+    active: Res<ActiveScenario>,
+    seed: Res<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
+    allocator: Res<ActiveAllocator>,
+    planner: Res<ActivePlanner>,
+    traffic: Res<TrafficControl>,
+    deadlocks: Res<DeadlockCount>,
+    forecast: Res<ConflictForecast>,
+    config: Res<ScenarioConfig>,
+    robots: Query<(&RobotPath, &CollisionState, &Battery), With<Robot>>,
+    tasks: Query<&Task>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    // Counting tasks each tick adds up over long shifts, so wait for arrivals to end first.
+    if !arrivals.is_exhausted() && sim.now < settings.time_limit {
+        return;
+    }
+    let total = tasks.iter().count();
+    let completed = tasks.iter().filter(|task| task.completed).count();
+    let unreachable = tasks.iter().filter(|task| task.unreachable).count();
+    let drained = completed + unreachable == total && arrivals.is_exhausted();
+    // Unreachable tasks can never complete, so the run is over once only they remain.
+    if !drained && sim.now < settings.time_limit {
+        return;
+    }
+
+    let distance: f32 = robots
+        .iter()
+        .map(|(path, ..)| {
+            path.points
+                .windows(2)
+                .map(|w| w[0].distance(w[1]))
+                .sum::<f32>()
+        })
+        .sum();
+    let conflicts: u32 = robots
+        .iter()
+        .map(|(_, collision, _)| collision.conflict_count)
+        .sum();
+    let battery = config.battery.map(|_| {
+        robots
+            .iter()
+            .fold((0, 1.0f32), |(charges, lowest), (_, _, battery)| {
+                (charges + battery.charges, lowest.min(battery.lowest))
+            })
+    });
+
+    let lead_times: Vec<f64> = tasks
+        .iter()
+        .filter_map(|task| task.completed_at.map(|done| done - task.released_at))
+        .collect();
+    let mean_lead_time =
+        (!lead_times.is_empty()).then(|| lead_times.iter().sum::<f64>() / lead_times.len() as f64);
+    let throughput = if sim.now > 0.0 {
+        completed as f64 / sim.now * 60.0
+    } else {
+        0.0
+    };
+
+    let deadlines = tasks.iter().any(|task| task.due.is_some()).then(|| {
+        let mut report = DeadlineReport::default();
+        for task in tasks.iter() {
+            let Some(due) = task.due else {
+                continue;
+            };
+            match task.tardiness() {
+                Some(tardiness) if tardiness > 0.0 => {
+                    report.late += 1;
+                    report.tardiness += tardiness;
+                }
+                Some(_) => report.on_time += 1,
+                None if due < sim.now => report.overdue += 1,
+                None => {}
+            }
+        }
+        report
+    });
+
+    let open_ended = !arrivals.is_exhausted();
+    let status = if drained && completed == total {
+        "completed"
+    } else if drained {
+        "unreachable tasks"
+    } else if open_ended {
+        "time limit reached (arrivals ongoing)"
+    } else {
+        "time limit reached"
+    };
+    let summary = RunSummary {
+        scenario: library.label(active.0).to_string(),
+        seed: seed.0,
+        allocator: allocator.kind,
+        planner: planner.kind,
+        status,
+        sim_time: sim.now,
+        event_driven: settings.event_driven,
+        ticks: 0,
+        wall_time: Duration::ZERO,
+        total,
+        completed,
+        unreachable,
+        robots: robots.iter().count(),
+        distance,
+        conflicts,
+        swept_conflicts: forecast.occurred.len(),
+        rejected_events: sim.rejected,
+        deadlocks: traffic.policy.map(|policy| (policy, deadlocks.0)),
+        throughput,
+        mean_lead_time,
+        open_ended,
+        battery,
+        deadlines,
+    };
+
+    exit.write(if summary.succeeded() {
+        AppExit::Success
+    } else {
+        AppExit::error()
+    });
+    commands.insert_resource(summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::DEFAULT_SIM_STEP;
+    use crate::reservation::DEFAULT_ECBS_WEIGHT;
+
+    fn options(seed: u64, deadlock_policy: Option<DeadlockPolicy>) -> HeadlessOptions {
+        HeadlessOptions {
+            scenarios: vec![Scenario::StressTest],
+            time_limit: 60.0,
+            step: DEFAULT_SIM_STEP,
+            event_driven: false,
+            seed,
+            allocator: AllocatorKind::Greedy,
+            auction: AuctionSettings::default(),
+            weights: TaskWeights::default(),
+            planner: PlannerKind::Independent,
+            ecbs_weight: DEFAULT_ECBS_WEIGHT,
+            deadlock_policy,
+            compare_allocators: false,
+            library: ScenarioLibrary::default(),
+        }
+    }
+
+    #[test]
+    fn same_seed_runs_execute_identical_events() {
+        for policy in [None, Some(DeadlockPolicy::BackOff)] {
+            for seed in [1, 42] {
+                let options = options(seed, policy);
+                let (_, first) = traced_run(Scenario::StressTest, &options, seed);
+                let (_, second) = traced_run(Scenario::StressTest, &options, seed);
+                assert!(!first.is_empty());
+                assert_eq!(first, second, "seed {seed}, policy {policy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn event_driven_runs_stop_at_the_time_limit() {
+        let options = HeadlessOptions {
+            time_limit: 3.0,
+            event_driven: true,
+            ..options(42, None)
+        };
+        let (_, trace) = traced_run(Scenario::StressTest, &options, 42);
+        assert!(!trace.is_empty());
+        for line in &trace {
+            let timestamp: f64 = line.split(' ').next().unwrap().parse().unwrap();
+            assert!(timestamp <= options.time_limit, "{line}");
+        }
+    }
+
+    #[test]
+    fn determinism_check_rejects_seeds_that_overflow() {
+        let options = options(u64::MAX - 1, None);
+        assert_eq!(run_determinism_check(&options), AppExit::error());
+    }
+}
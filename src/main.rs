@@ -1,22 +1,132 @@
 use bevy::prelude::*;
 
+// Task allocation strategies.
+mod allocation;
+// Tasks released over simulation time.
+mod arrivals;
 // Camera controls and camera entity setup.
 mod camera;
+// Command-line argument parsing.
+mod cli;
+// Swept-volume conflict prediction along robot routes.
+mod conflicts;
+// Windowless batch runner for CI and regression runs.
+mod headless;
+// Warehouse occupancy grid and static obstacles.
+mod map;
+// Joint multi-robot path finding (CBS / ECBS).
+mod mapf;
 // Shared components/resources and helper functions.
 mod model;
+// Grid path planning for robot moves.
+mod pathfinding;
+// Space-time route reservations and planning around them.
+mod reservation;
+// Loading and validation of TOML/JSON scenario files.
+mod scenario_file;
 // World spawning and simulation systems.
 mod simulation;
+// Uniform-grid index of robot positions for neighbour queries.
+mod spatial;
+// Robots yielding to each other, and deadlock detection between them.
+mod traffic;
 // UI setup and interaction systems.
 mod ui;
 
-use crate::model::{ActiveScenario, Scenario, Simulation};
+use crate::allocation::ActiveAllocator;
+use crate::cli::CliArgs;
+use crate::headless::HeadlessOptions;
+use crate::model::{
+    ActiveScenario, Scenario, ScenarioLibrary, Simulation, SimulationClock, SimulationSeed,
+};
+use crate::reservation::ActivePlanner;
+use crate::traffic::TrafficControl;
+
+// Entry point: parse arguments, then either run headless or wire the interactive app.
+fn main() -> AppExit {
+    let args = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{}", cli::USAGE);
+            return AppExit::error();
+        }
+    };
+
+    if args.help {
+        println!("{}", cli::USAGE);
+        return AppExit::Success;
+    }
+
+    let library = match load_library(&args) {
+        Ok(library) => library,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return AppExit::error();
+        }
+    };
+
+    let mut scenarios = Vec::new();
+    for name in &args.scenarios {
+        let Some(scenario) = library.find(name) else {
+            eprintln!("error: unknown scenario `{name}`\n\n{}", cli::USAGE);
+            return AppExit::error();
+        };
+        scenarios.push(scenario);
+    }
+
+    if args.headless
+        || args.compare_allocators
+        || args.benchmark
+        || args.check_determinism
+        || args.event_driven
+    {
+        if scenarios.is_empty() {
+            scenarios = library.scenarios();
+        }
+        let options = HeadlessOptions {
+            scenarios,
+            time_limit: args.time_limit,
+            step: args.step,
+            event_driven: args.event_driven,
+            seed: args.seed,
+            allocator: args.allocator,
+            auction: args.auction,
+            weights: args.weights,
+            planner: args.planner,
+            ecbs_weight: args.ecbs_weight,
+            deadlock_policy: args.deadlock_policy,
+            compare_allocators: args.compare_allocators,
+            library,
+        };
+        if args.benchmark {
+            return headless::run_benchmark(&options);
+        }
+        if args.check_determinism {
+            return headless::run_determinism_check(&options);
+        }
+        return headless::run_batch(&options);
+    }
+
+    let scenario = scenarios.first().copied().unwrap_or(Scenario::Warehouse);
 
-// Entry point: wire plugins, resources, startup systems, and frame systems.
-fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .insert_resource(Simulation::new())
-        .insert_resource(ActiveScenario(Scenario::Warehouse))
+        .insert_resource(SimulationClock {
+            step: args.step,
+            ..SimulationClock::new()
+        })
+        .insert_resource(ActiveScenario(scenario))
+        .insert_resource(library)
+        .insert_resource(SimulationSeed(args.seed))
+        .init_resource::<ui::SeedEntry>()
+        .insert_resource(ActiveAllocator::new(
+            args.allocator,
+            args.auction,
+            args.weights,
+        ))
+        .insert_resource(ActivePlanner::new(args.planner, args.ecbs_weight))
+        .insert_resource(TrafficControl::new(args.deadlock_policy))
         .add_systems(
             Startup,
             (
@@ -25,21 +135,68 @@ fn main() {
                 ui::setup_restart_ui,
             ),
         )
+        .add_systems(
+            FixedUpdate,
+            (
+                simulation::run_simulation,
+                simulation::update_robot_motion,
+                simulation::update_spatial_index,
+                simulation::yield_to_robots,
+                simulation::resolve_deadlocks,
+                simulation::update_batteries,
+                simulation::carry_task_markers,
+                simulation::allocate_tasks,
+                simulation::detect_conflicts,
+                simulation::predict_conflicts,
+            )
+                .chain(),
+        )
         .add_systems(
             Update,
             (
                 camera::camera_movement,
                 (
-                    simulation::run_simulation,
-                    simulation::allocate_tasks,
-                    simulation::detect_conflicts,
+                    simulation::sync_fixed_timestep,
+                    simulation::interpolate_robot_transforms,
+                    simulation::resize_ground,
+                    map::spawn_map_meshes,
+                    simulation::attach_robot_visuals,
+                    simulation::attach_task_visuals,
+                    simulation::attach_station_visuals,
                     simulation::highlight_collisions,
                     simulation::draw_robot_paths,
+                    simulation::draw_task_dropoffs,
+                    simulation::draw_task_dependencies,
+                    simulation::draw_conflict_forecast,
+                    simulation::draw_battery_bars,
                 )
                     .chain(),
                 ui::scenario_button_system,
                 ui::restart_button_system,
+                ui::new_seed_button_system,
+                ui::allocator_button_system,
+                ui::clock_button_system,
+                ui::clock_keyboard_system,
+                ui::update_clock_status,
             ),
         )
-        .run();
+        .run()
+}
+
+// Builds the scenario library from ./scenarios plus any `--scenario-file` paths.
+//
+// Broken files in the directory are reported and skipped; an explicitly requested file
+// that fails to load is a hard error.
+fn load_library(args: &CliArgs) -> Result<ScenarioLibrary, scenario_file::ScenarioFileError> {
+    let (mut custom, errors) =
+        scenario_file::load_scenario_dir(std::path::Path::new(scenario_file::SCENARIO_DIR));
+    for err in errors {
+        eprintln!("warning: skipping scenario file: {err}");
+    }
+
+    for path in &args.scenario_files {
+        custom.push(scenario_file::load_scenario_file(path)?);
+    }
+
+    Ok(ScenarioLibrary { custom })
 }
@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::model::ScenarioConfig;
+
+/// Side length of one occupancy cell used by the built-in presets.
+pub const DEFAULT_CELL_SIZE: f32 = 0.5;
+
+/// What occupies a grid cell.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellKind {
+    /// Drivable floor.
+    Free,
+    /// Storage rack; blocks robots.
+    Shelf,
+    /// Building wall; blocks robots.
+    Wall,
+    /// Column or pillar; blocks robots.
+    Pillar,
+    /// Floor robots must not enter (e.g. a pedestrian walkway).
+    NoGo,
+}
+
+impl CellKind {
+    /// True for every kind robots may not drive through.
+    pub fn is_blocking(self) -> bool {
+        self != CellKind::Free
+    }
+
+    /// Render height and colour for obstacle meshes of this kind.
+    fn appearance(self) -> (f32, Color) {
+        match self {
+            CellKind::Free => (0.0, Color::NONE),
+            CellKind::Shelf => (1.6, Color::srgb(0.55, 0.38, 0.2)),
+            CellKind::Wall => (2.2, Color::srgb(0.45, 0.45, 0.5)),
+            CellKind::Pillar => (3.0, Color::srgb(0.3, 0.3, 0.35)),
+            CellKind::NoGo => (0.02, Color::srgb(0.95, 0.8, 0.1)),
+        }
+    }
+}
+
+/// An axis-aligned rectangle of blocked floor, in world `x`/`z` coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub kind: CellKind,
+    /// Minimum `[x, z]` corner.
+    pub min: Vec2,
+    /// Maximum `[x, z]` corner.
+    pub max: Vec2,
+}
+
+impl Obstacle {
+    /// Creates an obstacle spanning `min..max` on the floor.
+    pub fn new(kind: CellKind, min: Vec2, max: Vec2) -> Self {
+        Self { kind, min, max }
+    }
+
+    /// True if the floor point `(x, z)` lies inside the rectangle grown by `margin`.
+    pub fn contains(&self, point: Vec2, margin: f32) -> bool {
+        point.cmpge(self.min - margin).all() && point.cmple(self.max + margin).all()
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+/// Occupancy grid of the active warehouse, rasterised from the scenario's obstacles.
+pub struct WarehouseMap {
+    /// Side length of one square cell in world units.
+    pub cell_size: f32,
+    /// Number of cells along `x`.
+    pub cols: i32,
+    /// Number of cells along `z`.
+    pub rows: i32,
+    /// World `x`/`z` of the minimum corner of cell `(0, 0)`.
+    pub origin: Vec2,
+    /// Row-major cell contents, `cols * rows` long.
+    cells: Vec<CellKind>,
+    /// Source rectangles, kept for rendering.
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl WarehouseMap {
+    /// Rasterises the scenario's obstacles onto a grid covering its arena.
+    ///
+    /// A cell takes the kind of the first obstacle whose rectangle contains its centre.
+    pub fn from_config(config: &ScenarioConfig) -> Self {
+        let cell_size = config.cell_size;
+        let cells_per_side = (config.arena_size / cell_size).ceil() as i32;
+        let half = cells_per_side as f32 * cell_size / 2.0;
+        let mut map = Self {
+            cell_size,
+            cols: cells_per_side,
+            rows: cells_per_side,
+            origin: Vec2::splat(-half),
+            cells: vec![CellKind::Free; (cells_per_side * cells_per_side) as usize],
+            obstacles: config.obstacles.clone(),
+        };
+
+        for row in 0..map.rows {
+            for col in 0..map.cols {
+                let cell = IVec2::new(col, row);
+                let center = map.cell_center_2d(cell);
+                if let Some(obstacle) = config.obstacles.iter().find(|o| o.contains(center, 0.0)) {
+                    let index = map.index(cell);
+                    map.cells[index] = obstacle.kind;
+                }
+            }
+        }
+        map
+    }
+
+    /// Returns the cell containing a world position, or `None` outside the grid.
+    pub fn world_to_cell(&self, position: Vec3) -> Option<IVec2> {
+        let local = (Vec2::new(position.x, position.z) - self.origin) / self.cell_size;
+        let cell = local.floor().as_ivec2();
+        self.in_bounds(cell).then_some(cell)
+    }
+
+    /// World-space centre of a cell at floor height.
+    pub fn cell_center(&self, cell: IVec2) -> Vec3 {
+        let center = self.cell_center_2d(cell);
+        Vec3::new(center.x, 0.0, center.y)
+    }
+
+    /// True if `cell` lies inside the grid.
+    pub fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.cols && cell.y < self.rows
+    }
+
+    /// Contents of a cell; everything outside the grid counts as wall.
+    pub fn kind(&self, cell: IVec2) -> CellKind {
+        if self.in_bounds(cell) {
+            self.cells[self.index(cell)]
+        } else {
+            CellKind::Wall
+        }
+    }
+
+    /// True if robots may not occupy `cell`.
+    pub fn is_blocked(&self, cell: IVec2) -> bool {
+        self.kind(cell).is_blocking()
+    }
+
+    /// True if robots may not occupy the cell under `position`.
+    pub fn is_blocked_at(&self, position: Vec3) -> bool {
+        self.world_to_cell(position)
+            .is_none_or(|cell| self.is_blocked(cell))
+    }
+
+    /// True if a robot of radius `clearance` centred in `cell` touches no blocked cell.
+    pub fn has_clearance(&self, cell: IVec2, clearance: f32) -> bool {
+        if self.is_blocked(cell) {
+            return false;
+        }
+        let reach = (clearance / self.cell_size).ceil() as i32;
+        let half = self.cell_size / 2.0;
+        for dz in -reach..=reach {
+            for dx in -reach..=reach {
+                let other = cell + IVec2::new(dx, dz);
+                if !self.is_blocked(other) {
+                    continue;
+                }
+                // Distance from this cell's centre to the nearest point of the blocked square.
+                let offset =
+                    (IVec2::new(dx, dz).as_vec2().abs() * self.cell_size - half).max(Vec2::ZERO);
+                if offset.length() < clearance {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Number of cells in the grid.
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn cell_center_2d(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    /// Row-major index of an in-bounds cell, for per-cell lookup tables.
+    pub fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.cols + cell.x) as usize
+    }
+}
+
+#[derive(Component)]
+/// Marker for rendered obstacle meshes, despawned when the map changes.
+pub struct MapObstacle;
+
+/// Rebuilds obstacle meshes whenever a new `WarehouseMap` is inserted.
+pub fn spawn_map_meshes(
+    mut commands: Commands,
+    map: Res<WarehouseMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    existing: Query<Entity, With<MapObstacle>>,
+) {
+    if !map.is_changed() {
+        return;
+    }
+    for entity in &existing {
+        commands.entity(entity).despawn();
+    }
+
+    for obstacle in &map.obstacles {
+        let (height, color) = obstacle.kind.appearance();
+        let size = obstacle.max - obstacle.min;
+        let center = (obstacle.min + obstacle.max) / 2.0;
+        commands.spawn((
+            Mesh3d(meshes.add(Cuboid::new(size.x, height, size.y))),
+            MeshMaterial3d(materials.add(color)),
+            Transform::from_xyz(center.x, height / 2.0, center.y),
+            MapObstacle,
+        ));
+    }
+}
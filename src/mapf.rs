@@ -0,0 +1,762 @@
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+use crate::map::WarehouseMap;
+use crate::reservation::{CLEARANCE_MARGIN, ReservationTable, closest_to_origin};
+
+/// Moves on the MAPF grid: wait in place or step to one of the four straight neighbours,
+/// as in the usual grid benchmarks.
+const MOVES: [IVec2; 5] = [IVec2::ZERO, IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+/// Constraint-tree nodes expanded before the joint search gives up.
+const MAX_TREE_NODES: usize = 500;
+/// Space-time states expanded per single-robot search.
+const MAX_STATES: usize = 40_000;
+/// Steps a single-robot search looks past its estimate and its latest constraint.
+const HORIZON_SLACK: u32 = 200;
+
+/// A robot to be routed by the joint planner.
+pub struct Agent {
+    /// Robot id, whose own reservation is disregarded.
+    pub robot_id: usize,
+    /// Footprint radius.
+    pub radius: f32,
+    /// Where the robot stands when the plan starts.
+    pub start: Vec3,
+    /// Positions to visit in order, each with the time to stay there; the robot stays at
+    /// the last one for good.
+    pub stops: Vec<(Vec3, f64)>,
+}
+
+/// Where a robot is during one step: its exact start, a grid cell centre, or the exact
+/// position of one of its stops.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Spot {
+    Start,
+    Cell(IVec2),
+    Stop(usize),
+}
+
+/// Forbids one robot the move `from` → `to` during `step`; waiting counts as a move.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Constraint {
+    agent: usize,
+    step: u32,
+    from: Spot,
+    to: Spot,
+}
+
+/// A robot's plan: the spot it is on at every step until it reaches its last stop.
+type Path = Vec<Spot>;
+
+/// Plans collision-free routes for all `agents` together with Conflict-Based Search.
+///
+/// Time is split into steps of one grid cell at `speed` (the slowest robot's top speed)
+/// and every robot moves in lockstep on the occupancy grid, taking straight steps between
+/// cells that keep its radius from obstacles. Conflicts are found by checking the robots'
+/// footprints along each step, and the search branches on which of the two robots may not
+/// make its move. Robots outside the batch are avoided through their reservations. With
+/// `weight` above 1 this is ECBS: both levels pick, among candidates costing at most
+/// `weight` times the current lower bound, the one with the fewest conflicts, and the plan
+/// costs at most `weight` times the optimum.
+///
+/// Returns the timed legs of every agent in the shape of `plan_reserved`, or `None` if no
+/// joint plan was found within the search budget.
+#[allow(clippy::type_complexity)]
+pub fn plan_joint(
+    table: &ReservationTable,
+    map: &WarehouseMap,
+    agents: &[Agent],
+    speed: f32,
+    now: f64,
+    weight: f32,
+) -> Option<Vec<Vec<Vec<(f64, Vec3)>>>> {
+    let mut joint = Joint {
+        table,
+        map,
+        problems: Vec::with_capacity(agents.len()),
+        batch: agents.iter().map(|agent| agent.robot_id).collect(),
+        now,
+        step_time: (map.cell_size / speed) as f64,
+        weight: weight.max(1.0),
+    };
+    for agent in agents {
+        let problem = joint.problem(agent)?;
+        joint.problems.push(problem);
+    }
+
+    let solution = joint.search()?;
+    Some(
+        solution
+            .iter()
+            .enumerate()
+            .map(|(agent, path)| joint.legs(agent, path))
+            .collect(),
+    )
+}
+
+/// What one robot's single searches need to know.
+struct Problem<'a> {
+    agent: &'a Agent,
+    start_cell: IVec2,
+    stop_cells: Vec<IVec2>,
+    /// Steps spent at each stop, covering its service time.
+    dwell: Vec<u32>,
+    /// Cells the robot fits in, by `map.index`.
+    passable: Vec<bool>,
+    /// Steps from every cell to each stop's cell, by stop and then `map.index`.
+    distances: Vec<Vec<u32>>,
+    /// Steps from leaving stop `i - 1` to reaching the last stop, by `i`.
+    remaining: Vec<u32>,
+    /// Step from which the last stop is clear of robots outside the batch for good.
+    free_from: u32,
+}
+
+/// A node of the constraint tree.
+struct TreeNode {
+    constraints: Vec<Constraint>,
+    paths: Vec<Path>,
+    /// Lower bound on each robot's path cost under these constraints.
+    bounds: Vec<u32>,
+    cost: u32,
+    conflicts: usize,
+}
+
+/// A state of a single-robot search.
+struct SearchNode {
+    spot: Spot,
+    /// Stops finished so far.
+    stage: usize,
+    step: u32,
+    conflicts: u32,
+    parent: Option<usize>,
+}
+
+struct Joint<'a> {
+    table: &'a ReservationTable,
+    map: &'a WarehouseMap,
+    problems: Vec<Problem<'a>>,
+    /// Robots being planned, whose old reservations are disregarded.
+    batch: Vec<usize>,
+    now: f64,
+    step_time: f64,
+    weight: f32,
+}
+
+impl<'a> Joint<'a> {
+    fn problem(&self, agent: &'a Agent) -> Option<Problem<'a>> {
+        let map = self.map;
+        let start_cell = map.world_to_cell(agent.start)?;
+        let stop_cells = agent
+            .stops
+            .iter()
+            .map(|&(stop, _)| map.world_to_cell(stop))
+            .collect::<Option<Vec<_>>>()?;
+        let &(last, _) = agent.stops.last()?;
+
+        let mut passable = vec![false; map.cell_count()];
+        for row in 0..map.rows {
+            for col in 0..map.cols {
+                let cell = IVec2::new(col, row);
+                passable[map.index(cell)] = map.has_clearance(cell, agent.radius);
+            }
+        }
+        for &cell in stop_cells.iter().chain([&start_cell]) {
+            passable[map.index(cell)] = !map.is_blocked(cell);
+        }
+        if stop_cells.iter().any(|&cell| map.is_blocked(cell)) {
+            return None;
+        }
+
+        let distances: Vec<Vec<u32>> = stop_cells
+            .iter()
+            .map(|&cell| grid_distances(map, &passable, cell))
+            .collect();
+        let dwell: Vec<u32> = agent
+            .stops
+            .iter()
+            .map(|&(_, service)| (service / self.step_time).ceil() as u32)
+            .collect();
+
+        let stops = stop_cells.len();
+        let mut remaining = vec![0; stops + 1];
+        for i in (1..stops).rev() {
+            let between = distances[i][map.index(stop_cells[i - 1])];
+            if between == u32::MAX {
+                return None;
+            }
+            remaining[i] = between + 2 + dwell[i] + remaining[i + 1];
+        }
+        if distances[0][map.index(start_cell)] == u32::MAX {
+            return None;
+        }
+
+        let clear = self
+            .table
+            .safe_intervals(&self.batch, agent.radius, last.xz(), self.now);
+        let (clear_from, _) = *clear.last().filter(|(_, end)| *end == f64::INFINITY)?;
+        let free_from = ((clear_from - self.now) / self.step_time).ceil().max(0.0) as u32;
+
+        Some(Problem {
+            agent,
+            start_cell,
+            stop_cells,
+            dwell,
+            passable,
+            distances,
+            remaining,
+            free_from,
+        })
+    }
+
+    /// High-level search over the constraint tree.
+    fn search(&self) -> Option<Vec<Path>> {
+        let agents = self.problems.len();
+        let mut root = TreeNode {
+            constraints: Vec::new(),
+            paths: vec![Vec::new(); agents],
+            bounds: vec![0; agents],
+            cost: 0,
+            conflicts: 0,
+        };
+        for agent in 0..agents {
+            let (path, bound) = self.plan_single(agent, &[], &root.paths)?;
+            root.paths[agent] = path;
+            root.bounds[agent] = bound;
+        }
+        self.evaluate(&mut root);
+
+        let mut open = vec![root];
+        for _ in 0..MAX_TREE_NODES {
+            // CBS expands the cheapest node; ECBS the least conflicted one within the bound.
+            let lower_bound = open
+                .iter()
+                .map(|node| node.bounds.iter().sum::<u32>())
+                .min()?;
+            let limit = lower_bound as f32 * self.weight;
+            let (index, _) = open
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| node.cost as f32 <= limit)
+                .min_by_key(|(_, node)| (node.conflicts, node.cost))
+                .or_else(|| open.iter().enumerate().min_by_key(|(_, node)| node.cost))?;
+            let node = open.swap_remove(index);
+
+            let Some((first, second, step)) = self.first_conflict(&node.paths) else {
+                return Some(node.paths);
+            };
+            for agent in [first, second] {
+                let path = &node.paths[agent];
+                let mut constraints = node.constraints.clone();
+                constraints.push(Constraint {
+                    agent,
+                    step,
+                    from: spot_at(path, step),
+                    to: spot_at(path, step + 1),
+                });
+                let Some((path, bound)) = self.plan_single(agent, &constraints, &node.paths) else {
+                    continue;
+                };
+                let mut child = TreeNode {
+                    constraints,
+                    paths: node.paths.clone(),
+                    bounds: node.bounds.clone(),
+                    cost: 0,
+                    conflicts: 0,
+                };
+                child.paths[agent] = path;
+                child.bounds[agent] = bound;
+                self.evaluate(&mut child);
+                open.push(child);
+            }
+        }
+        None
+    }
+
+    /// Fills in a tree node's cost and conflict count.
+    fn evaluate(&self, node: &mut TreeNode) {
+        node.cost = node.paths.iter().map(|path| path.len() as u32 - 1).sum();
+        node.conflicts = 0;
+        let horizon = node.paths.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        for a in 0..node.paths.len() {
+            for b in a + 1..node.paths.len() {
+                node.conflicts += (0..horizon)
+                    .filter(|&step| self.collide(a, &node.paths[a], b, &node.paths[b], step))
+                    .count();
+            }
+        }
+    }
+
+    /// Earliest step at which two robots' footprints meet, and which robots they are.
+    fn first_conflict(&self, paths: &[Path]) -> Option<(usize, usize, u32)> {
+        let horizon = paths.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        (0..horizon).find_map(|step| {
+            (0..paths.len()).find_map(|a| {
+                (a + 1..paths.len())
+                    .find(|&b| self.collide(a, &paths[a], b, &paths[b], step))
+                    .map(|b| (a, b, step))
+            })
+        })
+    }
+
+    /// True if robots `a` and `b` come too close while making their moves of `step`.
+    fn collide(&self, a: usize, path_a: &Path, b: usize, path_b: &Path, step: u32) -> bool {
+        let from =
+            self.position(a, spot_at(path_a, step)) - self.position(b, spot_at(path_b, step));
+        let to = self.position(a, spot_at(path_a, step + 1))
+            - self.position(b, spot_at(path_b, step + 1));
+        let distance = self.problems[a].agent.radius + self.problems[b].agent.radius;
+        closest_to_origin(from, to) < distance + CLEARANCE_MARGIN
+    }
+
+    /// Space-time A* for one robot under `constraints`, or focal search when ECBS
+    /// tie-breaks by conflicts with the other robots' current `paths`.
+    ///
+    /// Returns the path and a lower bound on its optimal cost.
+    fn plan_single(
+        &self,
+        agent: usize,
+        constraints: &[Constraint],
+        paths: &[Path],
+    ) -> Option<(Path, u32)> {
+        let problem = &self.problems[agent];
+        let stops = problem.stop_cells.len();
+        let last = Spot::Stop(stops - 1);
+        let forbidden: HashSet<(u32, Spot, Spot)> = constraints
+            .iter()
+            .filter(|constraint| constraint.agent == agent)
+            .map(|constraint| (constraint.step, constraint.from, constraint.to))
+            .collect();
+        // The robot may only settle at its last stop once nothing needs it gone.
+        let settle_after = forbidden
+            .iter()
+            .filter(|&&(_, from, to)| from == last && to == last)
+            .map(|&(step, ..)| step + 1)
+            .max()
+            .unwrap_or(0)
+            .max(problem.free_from);
+        let latest = forbidden.iter().map(|&(step, ..)| step).max().unwrap_or(0);
+        let estimate = self.heuristic(problem, Spot::Start, 0)?;
+        let horizon = estimate + latest.max(settle_after) + HORIZON_SLACK;
+
+        let mut nodes = vec![SearchNode {
+            spot: Spot::Start,
+            stage: 0,
+            step: 0,
+            conflicts: 0,
+            parent: None,
+        }];
+        let mut seen: HashSet<(Spot, usize, u32)> = HashSet::from([(Spot::Start, 0, 0)]);
+        let mut open: BTreeMap<u32, BTreeSet<(u32, Reverse<u32>, usize)>> = BTreeMap::new();
+        open.entry(estimate).or_default().insert((0, Reverse(0), 0));
+
+        for _ in 0..MAX_STATES {
+            let lower_bound = *open.keys().next()?;
+            let limit = ((lower_bound as f32 * self.weight).floor() as u32).max(lower_bound);
+            let (f, entry) = open
+                .range(lower_bound..=limit)
+                .filter_map(|(&f, entries)| entries.first().map(|&entry| (f, entry)))
+                .min_by_key(|&(f, (conflicts, step, id))| (conflicts, f, step, id))?;
+            let bucket = open.get_mut(&f)?;
+            bucket.remove(&entry);
+            if bucket.is_empty() {
+                open.remove(&f);
+            }
+
+            let id = entry.2;
+            let (spot, stage, step, conflicts) = {
+                let node = &nodes[id];
+                (node.spot, node.stage, node.step, node.conflicts)
+            };
+            if stage == stops && step >= settle_after {
+                return Some((trace(&nodes, id), lower_bound));
+            }
+            if step >= horizon {
+                continue;
+            }
+
+            for (next, next_stage, span) in self.successors(problem, spot, stage) {
+                let mut added = 0;
+                let mut allowed = true;
+                for offset in 0..span {
+                    let from = if offset == 0 { spot } else { next };
+                    let at = step + offset;
+                    if forbidden.contains(&(at, from, next)) || !self.clear(agent, from, next, at) {
+                        allowed = false;
+                        break;
+                    }
+                    if self.weight > 1.0 {
+                        added += self.conflicts_with(agent, from, next, at, paths);
+                    }
+                }
+                let arrival = step + span;
+                if !allowed || !seen.insert((next, next_stage, arrival)) {
+                    continue;
+                }
+                let Some(estimate) = self.heuristic(problem, next, next_stage) else {
+                    continue;
+                };
+                nodes.push(SearchNode {
+                    spot: next,
+                    stage: next_stage,
+                    step: arrival,
+                    conflicts: conflicts + added,
+                    parent: Some(id),
+                });
+                open.entry(arrival + estimate).or_default().insert((
+                    conflicts + added,
+                    Reverse(arrival),
+                    nodes.len() - 1,
+                ));
+            }
+        }
+        None
+    }
+
+    /// Moves out of `spot`, as (next spot, stops finished, steps taken). Reaching a stop
+    /// includes its dwell.
+    fn successors(&self, problem: &Problem, spot: Spot, stage: usize) -> Vec<(Spot, usize, u32)> {
+        let stops = problem.stop_cells.len();
+        match spot {
+            Spot::Start => vec![
+                (Spot::Start, stage, 1),
+                (Spot::Cell(problem.start_cell), stage, 1),
+            ],
+            Spot::Cell(cell) => {
+                let mut moves: Vec<(Spot, usize, u32)> = MOVES
+                    .iter()
+                    .map(|&offset| cell + offset)
+                    .filter(|&next| {
+                        self.map.in_bounds(next) && problem.passable[self.map.index(next)]
+                    })
+                    .map(|next| (Spot::Cell(next), stage, 1))
+                    .collect();
+                if stage < stops && problem.stop_cells[stage] == cell {
+                    moves.push((Spot::Stop(stage), stage + 1, 1 + problem.dwell[stage]));
+                }
+                moves
+            }
+            Spot::Stop(index) if stage < stops => vec![
+                (spot, stage, 1),
+                (Spot::Cell(problem.stop_cells[index]), stage, 1),
+            ],
+            Spot::Stop(_) => vec![(spot, stage, 1)],
+        }
+    }
+
+    /// Lower bound on the steps from `spot` to the end of the robot's last stop, or `None`
+    /// if the next stop cannot be reached from there.
+    fn heuristic(&self, problem: &Problem, spot: Spot, stage: usize) -> Option<u32> {
+        let stops = problem.stop_cells.len();
+        if stage == stops {
+            return Some(0);
+        }
+        let (cell, lead) = match spot {
+            Spot::Start => (problem.start_cell, 1),
+            Spot::Cell(cell) => (cell, 0),
+            Spot::Stop(index) => (problem.stop_cells[index], 1),
+        };
+        let distance = problem.distances[stage][self.map.index(cell)];
+        (distance != u32::MAX)
+            .then(|| lead + distance + 1 + problem.dwell[stage] + problem.remaining[stage + 1])
+    }
+
+    /// True if the move keeps clear of every robot outside the batch.
+    fn clear(&self, agent: usize, from: Spot, to: Spot, step: u32) -> bool {
+        let depart = self.now + step as f64 * self.step_time;
+        self.table.move_is_clear(
+            &self.batch,
+            self.problems[agent].agent.radius,
+            self.position(agent, from),
+            self.position(agent, to),
+            depart,
+            depart + self.step_time,
+        )
+    }
+
+    /// Number of other robots' current paths a move would run into.
+    fn conflicts_with(&self, agent: usize, from: Spot, to: Spot, step: u32, paths: &[Path]) -> u32 {
+        let (here, there) = (self.position(agent, from), self.position(agent, to));
+        let mut count = 0;
+        for (other, path) in paths.iter().enumerate() {
+            if other == agent || path.is_empty() {
+                continue;
+            }
+            let start = here - self.position(other, spot_at(path, step));
+            let end = there - self.position(other, spot_at(path, step + 1));
+            let distance = self.problems[agent].agent.radius + self.problems[other].agent.radius;
+            if closest_to_origin(start, end) < distance + CLEARANCE_MARGIN {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Floor position of a spot for one robot.
+    fn position(&self, agent: usize, spot: Spot) -> Vec2 {
+        let agent = self.problems[agent].agent;
+        match spot {
+            Spot::Start => agent.start.xz(),
+            Spot::Cell(cell) => self.map.cell_center(cell).xz(),
+            Spot::Stop(index) => agent.stops[index].0.xz(),
+        }
+    }
+
+    /// Splits a path into timed legs ending at each stop, keeping a waypoint at both ends
+    /// of every wait.
+    fn legs(&self, agent: usize, path: &Path) -> Vec<Vec<(f64, Vec3)>> {
+        let height = self.problems[agent].agent.start.y;
+        let timed = |step: usize| {
+            let point = self.position(agent, path[step]);
+            (
+                self.now + step as f64 * self.step_time,
+                Vec3::new(point.x, height, point.y),
+            )
+        };
+
+        let mut legs = Vec::new();
+        let mut from = 0;
+        for index in 0..self.problems[agent].stop_cells.len() {
+            let stop = Spot::Stop(index);
+            let arrive = path
+                .iter()
+                .position(|&spot| spot == stop)
+                .unwrap_or(path.len() - 1);
+            let mut leg = vec![timed(from)];
+            for step in from + 1..=arrive {
+                let moved = path[step] != path[step - 1];
+                let moves_next = step == arrive || path[step + 1] != path[step];
+                if moved || moves_next {
+                    leg.push(timed(step));
+                }
+            }
+            legs.push(leg);
+            from = path
+                .iter()
+                .rposition(|&spot| spot == stop)
+                .unwrap_or(arrive);
+        }
+        legs
+    }
+}
+
+/// The spot a path is on at `step`; robots stay at their last stop afterwards.
+fn spot_at(path: &Path, step: u32) -> Spot {
+    path[(step as usize).min(path.len() - 1)]
+}
+
+/// Rebuilds a path from the search tree, repeating a stop's spot over its dwell.
+fn trace(nodes: &[SearchNode], id: usize) -> Path {
+    let mut chain = vec![id];
+    while let Some(parent) = nodes[chain[chain.len() - 1]].parent {
+        chain.push(parent);
+    }
+    chain.reverse();
+
+    let mut path = vec![nodes[chain[0]].spot];
+    for pair in chain.windows(2) {
+        let (previous, node) = (&nodes[pair[0]], &nodes[pair[1]]);
+        path.extend(std::iter::repeat_n(
+            node.spot,
+            (node.step - previous.step) as usize,
+        ));
+    }
+    path
+}
+
+/// Straight-move grid distances to `goal` over passable cells (`u32::MAX` if unreachable).
+fn grid_distances(map: &WarehouseMap, passable: &[bool], goal: IVec2) -> Vec<u32> {
+    let mut distances = vec![u32::MAX; map.cell_count()];
+    distances[map.index(goal)] = 0;
+    let mut queue = VecDeque::from([goal]);
+    while let Some(cell) = queue.pop_front() {
+        let distance = distances[map.index(cell)];
+        for &offset in &MOVES[1..] {
+            let next = cell + offset;
+            if map.in_bounds(next)
+                && passable[map.index(next)]
+                && distances[map.index(next)] == u32::MAX
+            {
+                distances[map.index(next)] = distance + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CellKind, Obstacle};
+    use crate::model::{Scenario, ScenarioConfig, ScenarioLibrary};
+    use crate::reservation::DEFAULT_ECBS_WEIGHT;
+
+    /// A 10×10 arena with half-unit cells and the given walls.
+    fn map(walls: &[(Vec2, Vec2)]) -> WarehouseMap {
+        let config = ScenarioConfig {
+            arena_size: 10.0,
+            cell_size: 0.5,
+            obstacles: walls
+                .iter()
+                .map(|&(min, max)| Obstacle::new(CellKind::Wall, min, max))
+                .collect(),
+            ..ScenarioConfig::build(Scenario::Small, 0, &ScenarioLibrary::default())
+        };
+        WarehouseMap::from_config(&config)
+    }
+
+    /// Walls around a corridor along z = 0.25 from x = -2.5 to 2.5, with a one-cell bay
+    /// north of its middle if `bay` is set.
+    fn corridor(bay: bool) -> WarehouseMap {
+        let mut walls = vec![
+            (Vec2::new(-5.0, -5.0), Vec2::new(5.0, 0.0)),
+            (Vec2::new(-5.0, 1.0), Vec2::new(5.0, 5.0)),
+            (Vec2::new(-5.0, 0.0), Vec2::new(-2.5, 0.5)),
+            (Vec2::new(2.5, 0.0), Vec2::new(5.0, 0.5)),
+        ];
+        if bay {
+            walls.push((Vec2::new(-5.0, 0.5), Vec2::new(0.0, 1.0)));
+            walls.push((Vec2::new(0.5, 0.5), Vec2::new(5.0, 1.0)));
+        } else {
+            walls.push((Vec2::new(-5.0, 0.5), Vec2::new(5.0, 1.0)));
+        }
+        map(&walls)
+    }
+
+    fn agent(robot_id: usize, start: Vec2, goal: Vec2) -> Agent {
+        Agent {
+            robot_id,
+            radius: 0.2,
+            start: Vec3::new(start.x, 0.0, start.y),
+            stops: vec![(Vec3::new(goal.x, 0.0, goal.y), 0.0)],
+        }
+    }
+
+    /// Two robots starting at opposite ends of the corridor, each bound for the other end.
+    fn corridor_swap() -> [Agent; 2] {
+        [
+            agent(0, Vec2::new(-2.25, 0.25), Vec2::new(2.25, 0.25)),
+            agent(1, Vec2::new(2.25, 0.25), Vec2::new(-2.25, 0.25)),
+        ]
+    }
+
+    /// Where a robot on `route` is at `t`; it waits at the ends before and after.
+    fn position_at(route: &[(f64, Vec3)], t: f64) -> Vec2 {
+        let next = route.partition_point(|&(time, _)| time <= t);
+        if next == 0 {
+            return route[0].1.xz();
+        }
+        if next == route.len() {
+            return route[next - 1].1.xz();
+        }
+        let ((t0, p0), (t1, p1)) = (route[next - 1], route[next]);
+        p0.xz().lerp(p1.xz(), ((t - t0) / (t1 - t0)) as f32)
+    }
+
+    /// Checks the plans for vertex and edge conflicts on the step grid, and that the robots
+    /// keep their footprints apart in between; returns the sum of arrival steps.
+    fn assert_conflict_free(plans: &[Vec<Vec<(f64, Vec3)>>], step_time: f64) -> u32 {
+        let routes: Vec<&Vec<(f64, Vec3)>> = plans.iter().map(|legs| &legs[0]).collect();
+        let end = routes.iter().map(|r| r[r.len() - 1].0).fold(0.0, f64::max);
+        let steps = (end / step_time).round() as u32 + 1;
+        for a in 0..routes.len() {
+            for b in a + 1..routes.len() {
+                for step in 0..steps {
+                    let (t0, t1) = (step as f64 * step_time, (step + 1) as f64 * step_time);
+                    let (a0, b0) = (position_at(routes[a], t0), position_at(routes[b], t0));
+                    let (a1, b1) = (position_at(routes[a], t1), position_at(routes[b], t1));
+                    assert!(
+                        a0.distance(b0) > 0.1,
+                        "robots {a} and {b} share {a0} at {t0}"
+                    );
+                    let swapped = a0.distance(b1) < 0.1 && a1.distance(b0) < 0.1;
+                    assert!(!swapped, "robots {a} and {b} swap {a0} and {b0} at {t0}");
+                    for tenth in 0..=10 {
+                        let t = t0 + step_time * tenth as f64 / 10.0;
+                        let gap = position_at(routes[a], t).distance(position_at(routes[b], t));
+                        assert!(gap >= 0.4, "robots {a} and {b} {gap} apart at {t}");
+                    }
+                }
+            }
+        }
+        routes
+            .iter()
+            .map(|route| (route[route.len() - 1].0 / step_time).round() as u32)
+            .sum()
+    }
+
+    #[test]
+    fn corridor_swaps_use_the_bay() {
+        let map = corridor(true);
+        let agents = corridor_swap();
+        let table = ReservationTable::default();
+        let plans = plan_joint(&table, &map, &agents, 1.0, 0.0, 1.0).expect("a joint plan");
+        assert_eq!(plans.len(), 2);
+        for (plan, agent) in plans.iter().zip(&agents) {
+            let route = &plan[0];
+            assert_eq!(route[0].1, agent.start);
+            assert_eq!(route[route.len() - 1].1, agent.stops[0].0);
+        }
+        assert_conflict_free(&plans, 0.5);
+        // One of them has to step into the bay to let the other by.
+        let bay = Vec3::new(0.25, 0.0, 0.75);
+        assert!(
+            plans
+                .iter()
+                .any(|plan| plan[0].iter().any(|&(_, p)| p == bay))
+        );
+    }
+
+    #[test]
+    fn ecbs_stays_within_its_bound() {
+        let table = ReservationTable::default();
+        let (corridor, swap) = (corridor(true), corridor_swap());
+        let open = map(&[]);
+        let crossing = [
+            agent(0, Vec2::new(-2.25, 0.25), Vec2::new(2.25, 0.25)),
+            agent(1, Vec2::new(2.25, 0.75), Vec2::new(-2.25, 0.75)),
+            agent(2, Vec2::new(0.25, -2.25), Vec2::new(0.25, 2.25)),
+            agent(3, Vec2::new(0.75, 2.25), Vec2::new(0.75, -2.25)),
+        ];
+        let swap_and_cross = [
+            agent(0, Vec2::new(-1.75, 0.25), Vec2::new(1.75, 0.25)),
+            agent(1, Vec2::new(1.75, 0.25), Vec2::new(-1.75, 0.25)),
+            agent(2, Vec2::new(0.25, -1.75), Vec2::new(0.25, 1.75)),
+        ];
+        let instances = [
+            (&corridor, &swap[..], &[1.2, DEFAULT_ECBS_WEIGHT][..]),
+            (&open, &crossing[..], &[1.2, DEFAULT_ECBS_WEIGHT, 2.0][..]),
+            (
+                &open,
+                &swap_and_cross[..],
+                &[1.2, DEFAULT_ECBS_WEIGHT, 2.0][..],
+            ),
+        ];
+        for (map, agents, weights) in instances {
+            let optimal = plan_joint(&table, map, agents, 1.0, 0.0, 1.0).expect("a CBS plan");
+            let optimal = assert_conflict_free(&optimal, 0.5);
+            for &weight in weights {
+                let bounded =
+                    plan_joint(&table, map, agents, 1.0, 0.0, weight).expect("an ECBS plan");
+                let cost = assert_conflict_free(&bounded, 0.5);
+                assert!(cost >= optimal, "ECBS {cost} beat CBS {optimal}");
+                assert!(
+                    cost as f32 <= weight * optimal as f32,
+                    "{cost} over {weight} × {optimal}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hopeless_swaps_give_up() {
+        // Without the bay there is no way past; the search stops at its node budget so the
+        // caller can fall back to planning robots one at a time.
+        let map = corridor(false);
+        let agents = corridor_swap();
+        let table = ReservationTable::default();
+        assert!(plan_joint(&table, &map, &agents, 1.0, 0.0, 1.0).is_none());
+        assert!(plan_joint(&table, &map, &agents, 1.0, 0.0, DEFAULT_ECBS_WEIGHT).is_none());
+    }
+}
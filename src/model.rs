@@ -1,61 +1,436 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 
-/// The available simulation presets.
+use crate::allocation::AllocatorKind;
+use crate::arrivals::ArrivalProcess;
+use crate::map::{CellKind, DEFAULT_CELL_SIZE, Obstacle};
+use crate::reservation::PlannerKind;
+
+/// The available simulation presets, plus scenarios loaded from data files.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Scenario {
     Small,
     Warehouse,
     StressTest,
+    /// Open floor with this many robots, for measuring how the simulation scales; not part
+    /// of the preset row.
+    Benchmark(usize),
+    /// Index into `ScenarioLibrary::custom`.
+    Custom(usize),
+}
+
+impl Scenario {
+    /// Every built-in preset, in UI order.
+    pub const PRESETS: [Scenario; 3] = [Scenario::Small, Scenario::Warehouse, Scenario::StressTest];
+
+    /// Command-line names accepted for the built-in presets.
+    fn preset_names(self) -> &'static [&'static str] {
+        match self {
+            Scenario::Small => &["small"],
+            Scenario::Warehouse => &["warehouse"],
+            Scenario::StressTest => &["stress-test", "stresstest", "stress"],
+            Scenario::Benchmark(_) | Scenario::Custom(_) => &[],
+        }
+    }
+}
+
+/// Fleet sizes run by `--benchmark`; `--scenario benchmark` uses the middle one.
+pub const BENCHMARK_FLEETS: [usize; 3] = [500, 1000, 2000];
+
+/// Arena side length used by the built-in presets.
+pub const DEFAULT_ARENA_SIZE: f32 = 20.0;
+/// Robot travel speed (units/s) used by the built-in presets.
+pub const DEFAULT_ROBOT_SPEED: f32 = 4.0;
+/// Robot collision radius used by the built-in presets.
+pub const DEFAULT_COLLISION_RADIUS: f32 = 0.4;
+
+/// Spawn data for one task: where it is picked up and, optionally, where it is delivered.
+#[derive(Clone, Debug)]
+pub struct TaskSpec {
+    /// Pickup location; also where the task marker is placed.
+    pub position: Vec3,
+    /// Dropoff location, or `None` for a task that is done once loaded at `position`.
+    pub dropoff: Option<Vec3>,
+    /// Simulated seconds spent loading at the pickup.
+    pub pickup_service: f64,
+    /// Simulated seconds spent unloading at the dropoff.
+    pub dropoff_service: f64,
+    /// Payload the load needs; only robots whose type carries at least this much take it.
+    pub load: f32,
+    /// Higher values are allocated ahead of nearer work; `0` is normal.
+    pub priority: u32,
+    /// Sim time the task should be completed by, if it has a deadline.
+    pub due: Option<f64>,
+    /// Ids of tasks that must be completed before this one is allocated.
+    pub requires: Vec<usize>,
+}
+
+impl TaskSpec {
+    /// A standard-load task that only needs a robot to visit `position`.
+    pub fn at(position: Vec3) -> Self {
+        Self {
+            position,
+            dropoff: None,
+            pickup_service: 0.0,
+            dropoff_service: 0.0,
+            load: DEFAULT_LOAD,
+            priority: 0,
+            due: None,
+            requires: Vec::new(),
+        }
+    }
+}
+
+/// Load of tasks that do not set one, and the payload of the standard robot type.
+pub const DEFAULT_LOAD: f32 = 1.0;
+
+/// Body drawn for a robot type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RobotShape {
+    Box,
+    Cylinder,
+}
+
+/// Physical and visual parameters shared by every robot of one kind.
+#[derive(Component, Clone, Debug)]
+pub struct RobotType {
+    /// Name robots refer to in scenario files.
+    pub name: String,
+    /// Top speed in world units per second.
+    pub max_speed: f32,
+    /// Acceleration and braking in units/s²; `None` reaches top speed instantly.
+    pub acceleration: Option<f32>,
+    /// Footprint radius used for path clearance and conflicts.
+    pub radius: f32,
+    /// Heaviest task load the robot can take.
+    pub payload: f32,
+    pub shape: RobotShape,
+    pub color: Color,
+}
+
+impl RobotType {
+    /// The type used by the presets and by scenario files for robots without a `type`.
+    pub fn standard(max_speed: f32, radius: f32) -> Self {
+        Self {
+            name: "standard".to_string(),
+            max_speed,
+            acceleration: None,
+            radius,
+            payload: DEFAULT_LOAD,
+            shape: RobotShape::Box,
+            color: Color::srgb(0.2, 0.7, 0.3),
+        }
+    }
+
+    /// Seconds to drive a stretch of `length`, starting and ending at a standstill.
+    pub fn travel_time(&self, length: f32) -> f32 {
+        self.time_at(length, length)
+    }
+
+    /// Seconds after setting off at which `distance` of a `length` stretch is covered.
+    ///
+    /// With an acceleration the robot ramps up to top speed and brakes into the stop; on
+    /// stretches too short to reach top speed it turns around at the halfway point.
+    pub fn time_at(&self, distance: f32, length: f32) -> f32 {
+        let Some(accel) = self.acceleration else {
+            return distance / self.max_speed;
+        };
+        let ramp = (self.max_speed * self.max_speed / (2.0 * accel)).min(length / 2.0);
+        let peak = (2.0 * accel * ramp).sqrt();
+        let ramp_time = peak / accel;
+        if distance <= ramp {
+            (2.0 * distance / accel).sqrt()
+        } else if distance <= length - ramp {
+            ramp_time + (distance - ramp) / peak
+        } else {
+            let total = 2.0 * ramp_time + (length - 2.0 * ramp) / peak;
+            total - (2.0 * (length - distance).max(0.0) / accel).sqrt()
+        }
+    }
+}
+
+/// Spawn data for one robot.
+#[derive(Clone, Copy, Debug)]
+pub struct RobotSpec {
+    pub position: Vec3,
+    /// Starting battery charge, from 0 (flat) to 1 (full).
+    pub charge: f32,
+    /// Index into `ScenarioConfig::robot_types`.
+    pub kind: usize,
 }
 
-/// Resolved spawn data for a scenario: robot and task world positions.
+impl RobotSpec {
+    /// A standard robot at `position` with a full battery.
+    pub fn at(position: Vec3) -> Self {
+        Self {
+            position,
+            charge: 1.0,
+            kind: 0,
+        }
+    }
+}
+
+/// Battery behaviour for a scenario; all amounts are fractions of a full battery.
+#[derive(Clone, Copy, Debug)]
+pub struct BatteryConfig {
+    /// Charge used per world unit driven.
+    pub drain_per_unit: f32,
+    /// Charge used per simulated second while standing idle, loading or unloading.
+    pub idle_drain: f32,
+    /// Charge regained per simulated second at a charging station.
+    pub charge_rate: f32,
+    /// Robots without work go to charge once their level falls below this.
+    pub threshold: f32,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            drain_per_unit: 0.005,
+            idle_drain: 0.001,
+            charge_rate: 0.05,
+            threshold: 0.3,
+        }
+    }
+}
+
+/// Resolved spawn data for a scenario: positions plus the physical parameters of the run.
+///
+/// The active config is also kept as a resource so systems can read the map and battery
+/// settings.
+#[derive(Resource, Clone, Debug)]
 pub struct ScenarioConfig {
-    pub robot_positions: Vec<Vec3>,
-    pub task_positions: Vec<Vec3>,
+    pub robots: Vec<RobotSpec>,
+    /// Kinds of robot in the fleet; never empty.
+    pub robot_types: Vec<RobotType>,
+    pub tasks: Vec<TaskSpec>,
+    /// Side length of the square arena centred on the origin.
+    pub arena_size: f32,
+    /// Side length of one occupancy-grid cell.
+    pub cell_size: f32,
+    /// Static shelves, walls, pillars and no-go zones.
+    pub obstacles: Vec<Obstacle>,
+    /// Allocation strategy the scenario asks for; `None` keeps the current selection.
+    pub allocator: Option<AllocatorKind>,
+    /// Route planner the scenario asks for; `None` keeps the current selection.
+    pub planner: Option<PlannerKind>,
+    /// Processes releasing further tasks while the simulation runs.
+    pub arrivals: Vec<ArrivalProcess>,
+    /// Battery model; `None` gives robots unlimited energy.
+    pub battery: Option<BatteryConfig>,
+    /// Charging station positions, one robot per station at a time.
+    pub chargers: Vec<Vec3>,
+    /// Seed the config was built with; also drives randomly generated arrivals.
+    pub seed: u64,
 }
 
 impl ScenarioConfig {
-    pub fn build(scenario: Scenario) -> Self {
-        match scenario {
-            Scenario::Small => Self {
-                robot_positions: (0..3).map(robot_start_position).collect(),
-                task_positions: (0..8).map(task_position).collect(),
-            },
+    /// Resolves a scenario into positions; randomised layouts are drawn from `seed`.
+    pub fn build(scenario: Scenario, seed: u64, library: &ScenarioLibrary) -> Self {
+        let mut rng = SimRng::new(seed);
+        let mut arena_size = DEFAULT_ARENA_SIZE;
+        let (robots, tasks, obstacles) = match scenario {
+            Scenario::Small => (
+                (0..3)
+                    .map(|id| RobotSpec::at(robot_start_position(id, &mut rng)))
+                    .collect(),
+                (0..8)
+                    .map(|_| TaskSpec::at(task_position(&mut rng, &[])))
+                    .collect(),
+                Vec::new(),
+            ),
             Scenario::Warehouse => {
                 // 5 robots evenly spaced in a row at the bottom edge.
-                let robot_positions = (0..5)
-                    .map(|i| Vec3::new(i as f32 * 3.0 - 6.0, 0.5, -6.0))
+                let robots = (0..5)
+                    .map(|i| RobotSpec::at(Vec3::new(i as f32 * 3.0 - 6.0, 0.5, -6.0)))
                     .collect();
                 // 25 tasks in a 5-column × 5-row grid centred on the arena.
-                let task_positions = (0..25)
+                let tasks = (0..25)
                     .map(|i| {
                         let col = (i % 5) as f32;
                         let row = (i / 5) as f32;
-                        Vec3::new(col * 3.0 - 6.0, 0.25, row * 2.5 - 5.0)
+                        TaskSpec::at(Vec3::new(col * 3.0 - 6.0, 0.25, row * 2.5 - 5.0))
                     })
                     .collect();
-                Self { robot_positions, task_positions }
+                (robots, tasks, warehouse_obstacles())
             }
             Scenario::StressTest => {
                 // 10 robots in two rows of 5, well within the plane bounds (-10..10).
-                let robot_positions = (0..10)
+                let robots = (0..10)
                     .map(|i| {
                         let col = (i % 5) as f32;
                         let row = (i / 5) as f32;
-                        Vec3::new(col * 4.0 - 8.0, 0.5, row * 2.0 + 7.0)
+                        RobotSpec::at(Vec3::new(col * 4.0 - 8.0, 0.5, row * 2.0 + 7.0))
+                    })
+                    .collect();
+                let obstacles = stress_test_obstacles();
+                let tasks = (0..30)
+                    .map(|_| TaskSpec::at(task_position(&mut rng, &obstacles)))
+                    .collect();
+                (robots, tasks, obstacles)
+            }
+            Scenario::Benchmark(count) => {
+                // Robots on a square grid 2.0 apart, with one seeded random pick each
+                // within a few metres of a robot, so the fleet's density stays the same
+                // as it grows.
+                let side = (count as f32).sqrt().ceil() as usize;
+                let half = side as f32 - 1.0;
+                let robots = (0..count)
+                    .map(|i| {
+                        let (col, row) = ((i % side) as f32, (i / side) as f32);
+                        RobotSpec::at(Vec3::new(col * 2.0 - half, 0.5, row * 2.0 - half))
+                    })
+                    .collect();
+                let reach = half + 2.0;
+                let tasks = (0..count)
+                    .map(|i| {
+                        let (col, row) = ((i % side) as f32, (i / side) as f32);
+                        let x = (col * 2.0 - half + rng.range(-4.0, 4.0)).clamp(-reach, reach);
+                        let z = (row * 2.0 - half + rng.range(-4.0, 4.0)).clamp(-reach, reach);
+                        TaskSpec::at(Vec3::new(x, 0.25, z))
                     })
                     .collect();
-                Self {
-                    robot_positions,
-                    task_positions: (0..30).map(task_position).collect(),
-                }
+                arena_size = reach * 2.0 + 4.0;
+                (robots, tasks, Vec::new())
             }
+            Scenario::Custom(index) => {
+                return Self {
+                    seed,
+                    ..library.custom[index].config.clone()
+                };
+            }
+        };
+
+        Self {
+            robots,
+            robot_types: vec![RobotType::standard(
+                DEFAULT_ROBOT_SPEED,
+                DEFAULT_COLLISION_RADIUS,
+            )],
+            tasks,
+            arena_size,
+            cell_size: DEFAULT_CELL_SIZE,
+            obstacles,
+            allocator: None,
+            planner: None,
+            arrivals: Vec::new(),
+            battery: None,
+            chargers: Vec::new(),
+            seed,
         }
     }
 }
 
+/// A named scenario loaded from a data file.
+#[derive(Clone)]
+pub struct ScenarioDefinition {
+    /// Display name used on buttons, in reports and with `--scenario`.
+    pub name: String,
+    /// Fully resolved spawn data; file scenarios ignore the seed.
+    pub config: ScenarioConfig,
+}
+
+/// All scenarios available to the app: the built-in presets plus loaded files.
+#[derive(Resource, Clone, Default)]
+pub struct ScenarioLibrary {
+    /// File-backed scenarios, addressed by `Scenario::Custom(index)`.
+    pub custom: Vec<ScenarioDefinition>,
+}
+
+impl ScenarioLibrary {
+    /// Every available scenario, presets first, in UI order.
+    pub fn scenarios(&self) -> Vec<Scenario> {
+        Scenario::PRESETS
+            .into_iter()
+            .chain((0..self.custom.len()).map(Scenario::Custom))
+            .collect()
+    }
+
+    /// Human-readable name shown on buttons and in reports.
+    pub fn label(&self, scenario: Scenario) -> &str {
+        match scenario {
+            Scenario::Small => "Small",
+            Scenario::Warehouse => "Warehouse",
+            Scenario::StressTest => "Stress Test",
+            Scenario::Benchmark(_) => "Benchmark",
+            Scenario::Custom(index) => &self.custom[index].name,
+        }
+    }
+
+    /// Looks up a scenario by preset name (e.g. `stress-test`), `benchmark[-<robots>]`, or
+    /// loaded scenario name.
+    pub fn find(&self, name: &str) -> Option<Scenario> {
+        let lower = name.to_ascii_lowercase();
+        if lower == "benchmark" {
+            return Some(Scenario::Benchmark(BENCHMARK_FLEETS[1]));
+        }
+        if let Some(count) = lower
+            .strip_prefix("benchmark-")
+            .and_then(|n| n.parse().ok())
+            && count > 0
+        {
+            return Some(Scenario::Benchmark(count));
+        }
+        Scenario::PRESETS
+            .into_iter()
+            .find(|preset| preset.preset_names().contains(&lower.as_str()))
+            .or_else(|| {
+                self.custom
+                    .iter()
+                    .position(|def| def.name.eq_ignore_ascii_case(name))
+                    .map(Scenario::Custom)
+            })
+    }
+}
+
+/// Seed used when none is given on the command line.
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Seed for all randomised scenario generation; shown in the UI so runs can be reproduced.
+#[derive(Resource, Clone, Copy)]
+pub struct SimulationSeed(pub u64);
+
+/// Small deterministic PRNG (SplitMix64) so every run is reproducible from its seed.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    /// Creates a generator whose whole output stream is determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniform value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // Top 24 bits fill the f32 mantissa exactly.
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a uniform value in `[0, 1)` with full double precision.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a uniform value in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+}
+
 /// Active scenario resource — determines which preset is currently loaded.
 #[derive(Resource)]
 pub struct ActiveScenario(pub Scenario);
@@ -65,20 +440,172 @@ pub struct ActiveScenario(pub Scenario);
 pub struct Robot {
     /// Stable robot identifier used for task assignment and event routing.
     pub id: usize,
+    /// Spawn position, where the robot returns when it has to clear the way for another.
+    pub home: Vec3,
 }
 
 #[derive(Component, Default)]
-/// Tracks which task (if any) is currently assigned to a robot.
+/// Tracks which task (if any) is currently assigned to a robot, and the work lined up after it.
 pub struct RobotAssignment {
     /// The assigned task id, or `None` when the robot is idle.
     pub task_id: Option<usize>,
+    /// Tasks awarded to the robot but not started yet, in execution order.
+    pub queue: VecDeque<usize>,
+    /// Awards sent to the robot that have not reached it yet.
+    pub pending_awards: usize,
+    /// Driving home to make room for another robot.
+    pub repositioning: bool,
+}
+
+impl RobotAssignment {
+    /// True if the robot has no current, queued, or incoming work and is not on its way home.
+    pub fn is_idle(&self) -> bool {
+        self.task_id.is_none()
+            && self.queue.is_empty()
+            && self.pending_awards == 0
+            && !self.repositioning
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+/// What a robot is doing, driven by the events of its current task.
+pub enum RobotState {
+    /// No task in progress.
+    #[default]
+    Idle,
+    /// Driving to a pickup without a load.
+    TravellingEmpty,
+    /// Stopped at a pickup while the load is put on.
+    Loading,
+    /// Driving a load to its dropoff.
+    Carrying,
+    /// Stopped at a dropoff while the load is taken off.
+    Unloading,
+    /// Plugged into a charging station.
+    Charging,
+}
+
+#[derive(Component, Debug)]
+/// A robot's battery, present on every robot but only drained when the scenario has a
+/// battery model.
+pub struct Battery {
+    /// Charge left, from 0 (flat) to 1 (full).
+    pub level: f32,
+    /// Charging station the robot is heading to or plugged into.
+    pub station: Option<usize>,
+    /// Lowest level reached during the run.
+    pub lowest: f32,
+    /// Completed charging sessions.
+    pub charges: u32,
+    /// Sim time the level was last updated at.
+    pub last_time: f64,
+    /// Route length the robot had driven by the last update.
+    odometer: f32,
+    /// Length of the robot's visited history, and how many of its points that covers.
+    history: (f32, usize),
+}
+
+impl Battery {
+    /// A battery at `level` on a robot that has not moved yet.
+    pub fn new(level: f32) -> Self {
+        Self {
+            level,
+            station: None,
+            lowest: level,
+            charges: 0,
+            last_time: 0.0,
+            odometer: 0.0,
+            history: (0.0, 0),
+        }
+    }
+
+    /// Route length driven since the last call: the points added to the visited history in
+    /// `path` since, plus the way along the current route in `motion` by `now`.
+    pub fn drive(&mut self, path: &RobotPath, motion: &RobotMotion, now: f64) -> f32 {
+        let (length, counted) = self.history;
+        let added: f32 = path.points[counted.saturating_sub(1)..]
+            .windows(2)
+            .map(|w| w[0].distance(w[1]))
+            .sum();
+        self.history = (length + added, path.points.len());
+        let along = motion
+            .waypoints
+            .first()
+            .map_or(0.0, |&(start, _)| motion.distance_between(start, now));
+        let odometer = self.history.0 + along;
+        let driven = (odometer - self.odometer).max(0.0);
+        self.odometer = odometer;
+        driven
+    }
+}
+
+#[derive(Component)]
+/// A charging station robots drive to when their battery runs low.
+pub struct ChargingStation {
+    /// Stable station identifier, referenced by `Battery::station`.
+    pub id: usize,
 }
 
 #[derive(Component, Default)]
-/// Stores the sequence of positions visited by a robot.
+/// Stores the positions a robot has visited and the route it is currently following.
 pub struct RobotPath {
     /// Ordered waypoints used to draw a path line in the scene.
     pub points: Vec<Vec3>,
+    /// Planned waypoints of the current move, from departure point to destination.
+    pub planned: Vec<Vec3>,
+}
+
+#[derive(Component, Default)]
+/// Time-parameterised route a robot is currently travelling along.
+pub struct RobotMotion {
+    /// `(sim time, position)` pairs in increasing time order; empty while stationary.
+    pub waypoints: Vec<(f64, Vec3)>,
+}
+
+impl RobotMotion {
+    /// Interpolates the robot position at sim time `t`, clamped to the route ends.
+    pub fn position_at(&self, t: f64) -> Option<Vec3> {
+        let (first_time, first_pos) = *self.waypoints.first()?;
+        if t <= first_time {
+            return Some(first_pos);
+        }
+
+        for pair in self.waypoints.windows(2) {
+            let (t0, p0) = pair[0];
+            let (t1, p1) = pair[1];
+            if t <= t1 {
+                let span = t1 - t0;
+                let alpha = if span > 0.0 {
+                    ((t - t0) / span) as f32
+                } else {
+                    1.0
+                };
+                return Some(p0.lerp(p1, alpha));
+            }
+        }
+
+        self.waypoints.last().map(|&(_, pos)| pos)
+    }
+
+    /// Length of the route driven between sim times `from` and `to`, following every
+    /// waypoint passed on the way.
+    pub fn distance_between(&self, from: f64, to: f64) -> f32 {
+        let (Some(start), Some(end)) = (self.position_at(from), self.position_at(to)) else {
+            return 0.0;
+        };
+        let passed = self
+            .waypoints
+            .iter()
+            .filter(|&&(t, _)| t > from && t < to)
+            .map(|&(_, point)| point);
+        let mut length = 0.0;
+        let mut last = start;
+        for point in passed.chain([end]) {
+            length += last.distance(point);
+            last = point;
+        }
+        length
+    }
 }
 
 #[derive(Component, Default)]
@@ -86,6 +613,21 @@ pub struct RobotPath {
 pub struct CollisionState {
     /// True when the robot is within collision distance of another robot.
     pub is_colliding: bool,
+    /// Number of times this robot has entered a collision state.
+    pub conflict_count: u32,
+}
+
+#[derive(Component, Default)]
+/// A robot's yielding and deadlock state, kept up to date while robots yield to each other.
+pub struct TrafficState {
+    /// Robot this one is holding position for, if any.
+    pub waiting_for: Option<usize>,
+    /// Sim time the robot's current hold or back-off manoeuvre ends.
+    pub held_until: f64,
+    /// True from a deadlock the robot is part of until it moves on.
+    pub deadlocked: bool,
+    /// Number of deadlocks this robot has been part of.
+    pub deadlock_count: u32,
 }
 
 #[derive(Component)]
@@ -97,8 +639,83 @@ pub struct Task {
     pub assigned_to: Option<usize>,
     /// Whether this task has already been completed.
     pub completed: bool,
+    /// Set when no robot in the fleet can reach or carry the task; such tasks are never
+    /// allocated.
+    pub unreachable: bool,
+    /// Payload needed to carry the load.
+    pub load: f32,
+    /// Smallest footprint radius the planner has failed to route to the task with; only
+    /// robots narrower than this are offered it.
+    pub max_radius: f32,
+    /// Where the load is delivered; `None` for tasks done once loaded at the marker.
+    pub dropoff: Option<Vec3>,
+    /// Simulated seconds spent loading at the pickup.
+    pub pickup_service: f64,
+    /// Simulated seconds spent unloading at the dropoff.
+    pub dropoff_service: f64,
+    /// Set once the load is on a robot.
+    pub picked_up: bool,
+    /// Sim time the task became available.
+    pub released_at: f64,
+    /// Sim time the task was completed.
+    pub completed_at: Option<f64>,
+    /// Allocation priority; higher is more important.
+    pub priority: u32,
+    /// Sim time the task should be completed by.
+    pub due: Option<f64>,
+    /// Prerequisite task ids; the task is held back until all of them are completed.
+    pub requires: Vec<usize>,
+}
+
+impl Task {
+    /// True if the task still needs a robot.
+    pub fn is_open(&self) -> bool {
+        !self.completed && !self.unreachable && self.assigned_to.is_none()
+    }
+
+    /// Seconds the task was completed after its due time; zero if on time or without one.
+    pub fn tardiness(&self) -> Option<f64> {
+        let done = self.completed_at?;
+        Some(self.due.map_or(0.0, |due| (done - due).max(0.0)))
+    }
 }
 
+#[derive(Resource, Default)]
+/// Robot and task entities by id, so events reach their entity without scanning the rest.
+///
+/// Rebuilt by `spawn_scenario` whenever a scenario is (re)spawned, and extended as tasks
+/// arrive.
+pub struct EntityIndex {
+    robots: HashMap<usize, Entity>,
+    tasks: HashMap<usize, Entity>,
+}
+
+impl EntityIndex {
+    /// Entity of the robot with id `robot_id`.
+    pub fn robot(&self, robot_id: usize) -> Option<Entity> {
+        self.robots.get(&robot_id).copied()
+    }
+
+    /// Entity of the task with id `task_id`.
+    pub fn task(&self, task_id: usize) -> Option<Entity> {
+        self.tasks.get(&task_id).copied()
+    }
+
+    /// Records the entity spawned for robot `robot_id`.
+    pub fn insert_robot(&mut self, robot_id: usize, entity: Entity) {
+        self.robots.insert(robot_id, entity);
+    }
+
+    /// Records the entity spawned for task `task_id`.
+    pub fn insert_task(&mut self, task_id: usize, entity: Entity) {
+        self.tasks.insert(task_id, entity);
+    }
+}
+
+#[derive(Component)]
+/// Marker for the ground plane, rescaled to the active arena size.
+pub struct Ground;
+
 #[derive(Component)]
 /// Runtime movement settings for the free-fly camera.
 pub struct FlyCamera {
@@ -109,21 +726,32 @@ pub struct FlyCamera {
 }
 
 #[derive(Resource, Clone)]
-/// Shared robot materials for normal and collision-highlighted states.
+/// Shared robot material for the collision-highlighted state.
 pub struct RobotVisualMaterials {
-    /// Material shown when a robot is not colliding.
-    pub normal: Handle<StandardMaterial>,
     /// Material shown when a robot is colliding.
     pub collision: Handle<StandardMaterial>,
+    /// Material shown while a robot is part of a deadlock.
+    pub deadlock: Handle<StandardMaterial>,
 }
 
+#[derive(Component, Clone)]
+/// A robot's own material in its type's colour, restored once it stops colliding.
+pub struct RobotMaterial(pub Handle<StandardMaterial>);
+
 #[derive(Resource)]
 /// Global simulation state and pending event queue.
 pub struct Simulation {
     /// Current simulation clock time in seconds.
     pub now: f64,
-    /// Priority queue of future events, ordered by earliest timestamp.
-    pub events: BinaryHeap<Event>,
+    /// Future events, popped by earliest timestamp, then `EventPriority`, then the order
+    /// they were scheduled in.
+    events: BinaryHeap<QueuedEvent>,
+    /// Sequence number given to the next scheduled event.
+    next_sequence: u64,
+    /// Events turned away by `schedule` for a non-finite timestamp.
+    pub rejected: u32,
+    /// Every executed event in order, when recording was asked for with `record_trace`.
+    trace: Option<Vec<String>>,
 }
 
 impl Simulation {
@@ -132,12 +760,188 @@ impl Simulation {
         Self {
             now: 0.0,
             events: BinaryHeap::new(),
+            next_sequence: 0,
+            rejected: 0,
+            trace: None,
+        }
+    }
+
+    /// Pushes a new event into the priority queue; returns `false` and drops the event if
+    /// its timestamp is NaN or infinite, which would have no place in the order.
+    pub fn schedule(&mut self, event: Event) -> bool {
+        if !event.timestamp.is_finite() {
+            warn!(
+                "dropping event with timestamp {}: {:?}",
+                event.timestamp, event
+            );
+            self.rejected += 1;
+            return false;
+        }
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.events.push(QueuedEvent {
+            priority: event.event_type.priority(),
+            sequence,
+            event,
+        });
+        true
+    }
+
+    /// Takes every pending event `stale` matches off the queue.
+    pub fn cancel(&mut self, mut stale: impl FnMut(&Event) -> bool) {
+        self.events.retain(|queued| !stale(&queued.event));
+    }
+
+    /// Timestamp of the next pending event.
+    pub fn next_event_time(&self) -> Option<f64> {
+        self.events.peek().map(|queued| queued.event.timestamp)
+    }
+
+    /// Pops the next event, however far ahead it is.
+    pub fn pop_next(&mut self) -> Option<Event> {
+        let queued = self.events.pop()?;
+        if let Some(trace) = &mut self.trace {
+            trace.push(format!(
+                "{:?} #{} {:?}",
+                queued.event.timestamp, queued.sequence, queued.event.event_type
+            ));
+        }
+        Some(queued.event)
+    }
+
+    /// Pops the next event if it is due by `now`.
+    pub fn pop_due(&mut self) -> Option<Event> {
+        if self.events.peek()?.event.timestamp > self.now {
+            return None;
+        }
+        self.pop_next()
+    }
+
+    /// Drops every pending event and rewinds the clock to `0.0`, for a fresh run.
+    pub fn restart(&mut self) {
+        self.now = 0.0;
+        self.events.clear();
+        self.next_sequence = 0;
+        self.rejected = 0;
+        if let Some(trace) = &mut self.trace {
+            trace.clear();
+        }
+    }
+
+    /// Starts recording every executed event, for comparing runs.
+    pub fn record_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// The executed events recorded since `record_trace`, one line each.
+    pub fn trace(&self) -> &[String] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    /// Pushes a robot's scheduled events at or after `from` back by `by` seconds.
+    pub fn delay_robot(&mut self, robot_id: usize, from: f64, by: f64) {
+        if !by.is_finite() {
+            return;
+        }
+        let mut events = std::mem::take(&mut self.events).into_vec();
+        for queued in &mut events {
+            let event = &mut queued.event;
+            if event.timestamp >= from && event.event_type.robot_id() == Some(robot_id) {
+                event.timestamp += by;
+            }
+        }
+        self.events = events.into();
+    }
+
+    /// Drops every pending event scheduled for a robot.
+    pub fn cancel_robot(&mut self, robot_id: usize) {
+        self.events
+            .retain(|queued| queued.event.event_type.robot_id() != Some(robot_id));
+    }
+
+    /// Timestamp of the robot's next scheduled event.
+    pub fn next_robot_event(&self, robot_id: usize) -> Option<f64> {
+        self.events
+            .iter()
+            .filter(|queued| queued.event.event_type.robot_id() == Some(robot_id))
+            .map(|queued| queued.event.timestamp)
+            .min_by(f64::total_cmp)
+    }
+}
+
+/// Simulated seconds advanced per simulation tick unless `--step` says otherwise.
+pub const DEFAULT_SIM_STEP: f64 = 0.05;
+
+/// Speed multipliers the clock steps through with the faster/slower controls.
+pub const SPEED_STEPS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0];
+
+#[derive(Resource)]
+/// Playback state for the simulation clock: pause, single-step, and speed.
+pub struct SimulationClock {
+    /// When true, simulation time only advances through explicit steps.
+    pub paused: bool,
+    /// How many simulated seconds pass per real second, clamped to the `SPEED_STEPS` range;
+    /// sets how often the simulation ticks, not how far each tick goes.
+    pub speed: f64,
+    /// Simulated seconds advanced per tick, whatever the speed or frame rate.
+    pub step: f64,
+    /// Jump from event to event instead of advancing by `step`, ticking on only while no
+    /// event is pending.
+    pub event_driven: bool,
+    /// Shortest jump an event-driven tick makes, in simulated seconds; events due sooner run
+    /// late, at its end.
+    pub min_jump: f64,
+    /// Longest jump an event-driven tick may make, in simulated seconds.
+    pub max_jump: f64,
+    /// Simulated time the clock stops at; events due after it stay queued.
+    pub until: f64,
+    /// Set by the UI to execute exactly one pending event on the next frame.
+    pub step_requested: bool,
+}
+
+impl SimulationClock {
+    /// Creates a running clock at real-time speed.
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            step: DEFAULT_SIM_STEP,
+            event_driven: false,
+            min_jump: 0.0,
+            max_jump: f64::INFINITY,
+            until: f64::INFINITY,
+            step_requested: false,
+        }
+    }
+
+    /// Real seconds between simulation ticks at the current speed.
+    pub fn tick_interval(&self) -> f64 {
+        self.step / self.speed
+    }
+
+    /// Toggles between paused and running.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses the clock and requests a single event step.
+    pub fn request_step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    /// Moves to the next faster preset, saturating at the maximum.
+    pub fn faster(&mut self) {
+        if let Some(&next) = SPEED_STEPS.iter().find(|&&s| s > self.speed) {
+            self.speed = next;
         }
     }
 
-    /// Pushes a new event into the priority queue.
-    pub fn schedule(&mut self, event: Event) {
-        self.events.push(event);
+    /// Moves to the next slower preset, saturating at the minimum.
+    pub fn slower(&mut self) {
+        if let Some(&prev) = SPEED_STEPS.iter().rev().find(|&&s| s < self.speed) {
+            self.speed = prev;
+        }
     }
 }
 
@@ -150,31 +954,74 @@ pub struct Event {
     pub event_type: EventType,
 }
 
-impl Ord for Event {
-    /// Reverses sort order so the smallest timestamp is popped first.
+/// Which of several events due at the same time runs first; earlier variants go first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum EventPriority {
+    /// A robot reaching a stop, finishing a service step or a charge, so the step a robot
+    /// is on ends before the next one starts.
+    Completion,
+    /// A robot setting off along a new route.
+    Departure,
+    /// An auction award reaching its robot.
+    Message,
+    /// New tasks being released.
+    Release,
+    /// A predicted conflict, confirmed once everything else at that time has happened.
+    Observation,
+}
+
+/// An event in the queue, with the tie-breakers that fix its place among events due at
+/// the same time.
+#[derive(Debug)]
+struct QueuedEvent {
+    event: Event,
+    priority: EventPriority,
+    /// Scheduling order, unique within a run.
+    sequence: u64,
+}
+
+impl Ord for QueuedEvent {
+    /// Reverses sort order so the smallest `(timestamp, priority, sequence)` is popped first.
     fn cmp(&self, other: &Self) -> Ordering {
-        other.timestamp.partial_cmp(&self.timestamp).unwrap_or(Ordering::Equal)
+        other
+            .event
+            .timestamp
+            .total_cmp(&self.event.timestamp)
+            .then(other.priority.cmp(&self.priority))
+            .then(other.sequence.cmp(&self.sequence))
     }
 }
 
-impl PartialOrd for Event {
+impl PartialOrd for QueuedEvent {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Event {
+impl PartialEq for QueuedEvent {
     fn eq(&self, other: &Self) -> bool {
-        self.timestamp == other.timestamp
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl Eq for Event {}
+impl Eq for QueuedEvent {}
 
 #[derive(Debug)]
 /// Concrete event payloads executed by the simulation loop.
 pub enum EventType {
-    /// Moves one robot to a target position and optionally marks a task complete.
+    /// Starts a robot travelling along a timed route; it arrives via a matching `MoveRobot`.
+    ///
+    /// For pickup-and-delivery tasks the route runs through the pickup, holding position there
+    /// for the loading time, and the stops in between are marked by the loading events.
+    DepartRobot {
+        /// Identifier of the robot to move.
+        robot_id: usize,
+        /// `(sim time, position)` waypoints from the current position to the destination.
+        route: Vec<(f64, Vec3)>,
+    },
+    /// Completes a robot's move at a target position and optionally marks a task complete.
+    ///
+    /// For a task this fires once the robot is done servicing its last stop.
     MoveRobot {
         /// Identifier of the robot to move.
         robot_id: usize,
@@ -183,24 +1030,272 @@ pub enum EventType {
         /// Task id to complete after arrival, if the move came from allocation.
         task_id: Option<usize>,
     },
+    /// The robot has reached a pickup and starts loading.
+    StartLoading {
+        /// Identifier of the robot.
+        robot_id: usize,
+    },
+    /// The load is on the robot, which now carries it to the dropoff.
+    FinishLoading {
+        /// Identifier of the robot.
+        robot_id: usize,
+        /// Identifier of the loaded task.
+        task_id: usize,
+    },
+    /// The robot has reached a dropoff and starts unloading; `MoveRobot` completes the task.
+    StartUnloading {
+        /// Identifier of the robot.
+        robot_id: usize,
+    },
+    /// The robot has reached its charging station and plugs in.
+    ChargeStart {
+        /// Identifier of the robot.
+        robot_id: usize,
+        /// Station position the robot stops at.
+        target: Vec3,
+    },
+    /// The robot's battery is full; it unplugs and frees the station.
+    ChargeEnd {
+        /// Identifier of the robot.
+        robot_id: usize,
+    },
+    /// Releases the next task(s) of one of the scenario's arrival processes.
+    TaskArrival {
+        /// Index of the process in `ScenarioConfig::arrivals`.
+        process: usize,
+        /// Per-process arrival counter; selects the order for replayed streams.
+        index: usize,
+    },
+    /// Delivers an auction award to a robot, which appends the task to its queue.
+    AwardTask {
+        /// Identifier of the winning robot.
+        robot_id: usize,
+        /// Identifier of the awarded task.
+        task_id: usize,
+    },
+    /// Two robots' footprints meet, as predicted from their routes; predictions made stale
+    /// by a route change since are ignored.
+    RobotConflict {
+        /// The two robots, lower id first.
+        robots: (usize, usize),
+        /// Point midway between the robots when they touch.
+        position: Vec3,
+    },
 }
 
-/// Lightweight deterministic pseudo-random helper used for task placement.
-fn pseudo_random(seed: f32) -> f32 {
-    let x = (seed * 12.9898).sin() * 43_758.547;
-    x - x.floor()
-}
+impl EventType {
+    /// The robot whose schedule the event belongs to; `None` for task arrivals, awards and
+    /// predicted conflicts, which are not steps of a route.
+    pub fn robot_id(&self) -> Option<usize> {
+        match *self {
+            EventType::DepartRobot { robot_id, .. }
+            | EventType::MoveRobot { robot_id, .. }
+            | EventType::StartLoading { robot_id }
+            | EventType::FinishLoading { robot_id, .. }
+            | EventType::StartUnloading { robot_id }
+            | EventType::ChargeStart { robot_id, .. }
+            | EventType::ChargeEnd { robot_id } => Some(robot_id),
+            EventType::TaskArrival { .. }
+            | EventType::AwardTask { .. }
+            | EventType::RobotConflict { .. } => None,
+        }
+    }
 
-/// Returns the deterministic start position for a robot id.
-fn robot_start_position(robot_id: usize) -> Vec3 {
-    Vec3::new(robot_id as f32 * 2.0 - 4.0, 0.5, 0.0)
+    /// Tie-break class among events due at the same time.
+    pub fn priority(&self) -> EventPriority {
+        match self {
+            EventType::MoveRobot { .. }
+            | EventType::StartLoading { .. }
+            | EventType::FinishLoading { .. }
+            | EventType::StartUnloading { .. }
+            | EventType::ChargeStart { .. }
+            | EventType::ChargeEnd { .. } => EventPriority::Completion,
+            EventType::DepartRobot { .. } => EventPriority::Departure,
+            EventType::AwardTask { .. } => EventPriority::Message,
+            EventType::TaskArrival { .. } => EventPriority::Release,
+            EventType::RobotConflict { .. } => EventPriority::Observation,
+        }
+    }
 }
 
-/// Returns the deterministic world position for a task id.
-fn task_position(task_id: usize) -> Vec3 {
+/// Returns a start position for a robot id: a spaced row with seeded jitter.
+fn robot_start_position(robot_id: usize, rng: &mut SimRng) -> Vec3 {
+    // Jitter stays under half the 2.0 spacing so neighbours never start overlapping.
     Vec3::new(
-        pseudo_random(task_id as f32 + 10.0) * 14.0 - 7.0,
-        0.25,
-        pseudo_random(task_id as f32 + 42.0) * 14.0 - 7.0,
+        robot_id as f32 * 2.0 - 4.0 + rng.range(-0.5, 0.5),
+        0.5,
+        rng.range(-1.5, 1.5),
     )
 }
+
+/// Returns a seeded random task position inside the arena, clear of every obstacle.
+fn task_position(rng: &mut SimRng, obstacles: &[Obstacle]) -> Vec3 {
+    loop {
+        let candidate = Vec2::new(rng.range(-7.0, 7.0), rng.range(-7.0, 7.0));
+        if !obstacles.iter().any(|o| o.contains(candidate, 0.5)) {
+            return Vec3::new(candidate.x, 0.25, candidate.y);
+        }
+    }
+}
+
+/// Warehouse preset layout: perimeter walls, four shelf racks between the task columns,
+/// and a no-go walkway behind the robot start row.
+fn warehouse_obstacles() -> Vec<Obstacle> {
+    let mut obstacles = vec![
+        Obstacle::new(
+            CellKind::Wall,
+            Vec2::new(-10.0, -10.0),
+            Vec2::new(10.0, -9.5),
+        ),
+        Obstacle::new(CellKind::Wall, Vec2::new(-10.0, 9.5), Vec2::new(10.0, 10.0)),
+        Obstacle::new(CellKind::Wall, Vec2::new(-10.0, -9.5), Vec2::new(-9.5, 9.5)),
+        Obstacle::new(CellKind::Wall, Vec2::new(9.5, -9.5), Vec2::new(10.0, 9.5)),
+        Obstacle::new(CellKind::NoGo, Vec2::new(-9.5, -8.5), Vec2::new(9.5, -7.5)),
+    ];
+    // Racks sit halfway between the 3.0-spaced task columns, leaving cross aisles at both ends.
+    obstacles.extend([-4.5, -1.5, 1.5, 4.5].map(|x| {
+        Obstacle::new(
+            CellKind::Shelf,
+            Vec2::new(x - 0.4, -4.0),
+            Vec2::new(x + 0.4, 4.0),
+        )
+    }));
+    obstacles
+}
+
+/// Stress-test preset layout: a 2x2 block of pillars in the middle of the task field.
+fn stress_test_obstacles() -> Vec<Obstacle> {
+    [(-4.0, -3.0), (4.0, -3.0), (-4.0, 3.0), (4.0, 3.0)]
+        .map(|(x, z)| {
+            Obstacle::new(
+                CellKind::Pillar,
+                Vec2::new(x - 0.5, z - 0.5),
+                Vec2::new(x + 0.5, z + 0.5),
+            )
+        })
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event of one of five priority classes, carrying `label` so it can be recognised
+    /// once popped.
+    fn labelled(class: u64, label: usize, timestamp: f64) -> Event {
+        let event_type = match class {
+            0 => EventType::ChargeEnd { robot_id: label },
+            1 => EventType::DepartRobot {
+                robot_id: label,
+                route: Vec::new(),
+            },
+            2 => EventType::AwardTask {
+                robot_id: 0,
+                task_id: label,
+            },
+            3 => EventType::TaskArrival {
+                process: 0,
+                index: label,
+            },
+            _ => EventType::RobotConflict {
+                robots: (label, label),
+                position: Vec3::ZERO,
+            },
+        };
+        Event {
+            timestamp,
+            event_type,
+        }
+    }
+
+    fn label(event: &Event) -> usize {
+        match event.event_type {
+            EventType::ChargeEnd { robot_id } | EventType::DepartRobot { robot_id, .. } => robot_id,
+            EventType::AwardTask { task_id, .. } => task_id,
+            EventType::TaskArrival { index, .. } => index,
+            EventType::RobotConflict { robots, .. } => robots.0,
+            _ => unreachable!(),
+        }
+    }
+
+    fn drain(sim: &mut Simulation) -> Vec<usize> {
+        std::iter::from_fn(|| sim.pop_next())
+            .map(|event| label(&event))
+            .collect()
+    }
+
+    #[test]
+    fn equal_timestamps_pop_by_priority_then_scheduling_order() {
+        let mut sim = Simulation::new();
+        sim.schedule(labelled(4, 0, 1.0));
+        sim.schedule(labelled(1, 1, 1.0));
+        sim.schedule(labelled(3, 2, 1.0));
+        sim.schedule(labelled(1, 3, 1.0));
+        sim.schedule(labelled(0, 4, 1.0));
+        sim.schedule(labelled(2, 5, 0.5));
+        assert_eq!(drain(&mut sim), [5, 4, 1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn events_pop_in_timestamp_priority_sequence_order() {
+        for seed in 0..200 {
+            let mut rng = SimRng::new(seed);
+            let mut sim = Simulation::new();
+            // Few distinct timestamps, so most events tie with others.
+            let events: Vec<(f64, EventPriority, usize)> = (0..64)
+                .map(|label| {
+                    let timestamp = (rng.next_u64() % 6) as f64 * 0.25;
+                    let event = labelled(rng.next_u64() % 5, label, timestamp);
+                    let priority = event.event_type.priority();
+                    assert!(sim.schedule(event));
+                    (timestamp, priority, label)
+                })
+                .collect();
+            let mut expected = events.clone();
+            expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+            let expected: Vec<usize> = expected.iter().map(|&(.., label)| label).collect();
+            assert_eq!(drain(&mut sim), expected, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn batteries_drain_with_the_route_driven_not_the_straight_line() {
+        let corner = [
+            Vec3::ZERO,
+            Vec3::new(3.0, 0.0, 0.0),
+            Vec3::new(3.0, 0.0, 4.0),
+        ];
+        let mut path = RobotPath {
+            points: vec![corner[0]],
+            planned: corner.to_vec(),
+        };
+        let mut motion = RobotMotion {
+            waypoints: vec![(0.0, corner[0]), (1.0, corner[1]), (2.0, corner[2])],
+        };
+        let mut battery = Battery::new(1.0);
+        assert_eq!(battery.drive(&path, &motion, 0.5), 1.5);
+        assert_eq!(battery.drive(&path, &motion, 2.0), 5.5);
+
+        // Arriving moves the route into the history without driving it twice.
+        path.points.extend(path.planned.drain(1..));
+        motion.waypoints.clear();
+        assert_eq!(battery.drive(&path, &motion, 3.0), 0.0);
+
+        // A whole route that starts and ends between two updates still counts the corner.
+        let mut battery = Battery::new(1.0);
+        assert_eq!(battery.drive(&path, &motion, 3.0), 7.0);
+    }
+
+    #[test]
+    fn schedule_rejects_non_finite_timestamps() {
+        let mut sim = Simulation::new();
+        for timestamp in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(!sim.schedule(labelled(0, 0, timestamp)));
+        }
+        assert_eq!(sim.rejected, 3);
+        assert_eq!(sim.next_event_time(), None);
+
+        assert!(sim.schedule(labelled(0, 1, f64::MAX)));
+        assert_eq!(sim.next_event_time(), Some(f64::MAX));
+    }
+}
@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::map::WarehouseMap;
+
+/// Cost of a straight grid step; diagonal steps cost `DIAGONAL_COST` (≈ 10·√2).
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// The eight neighbour offsets, straight moves first.
+pub const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+/// Plans a collision-free route from `start` to `goal` on the warehouse grid.
+///
+/// Runs 8-connected A* over cells that keep `clearance` from every obstacle (diagonals may
+/// not cut corners), then shortens the cell chain with line-of-sight smoothing. The
+/// returned waypoints begin at `start`, end at `goal` and keep `start.y`. Returns `None`
+/// when the goal is blocked or unreachable.
+pub fn plan_path(map: &WarehouseMap, start: Vec3, goal: Vec3, clearance: f32) -> Option<Vec<Vec3>> {
+    plan_path_around(map, start, goal, clearance, &[])
+}
+
+/// Like `plan_path`, but also keeps out of the `(centre, radius)` discs in `avoid`, such as
+/// robots standing in the way.
+pub fn plan_path_around(
+    map: &WarehouseMap,
+    start: Vec3,
+    goal: Vec3,
+    clearance: f32,
+    avoid: &[(Vec3, f32)],
+) -> Option<Vec<Vec3>> {
+    let start_cell = map.world_to_cell(start)?;
+    let goal_cell = map.world_to_cell(goal)?;
+    if map.is_blocked(goal_cell) {
+        return None;
+    }
+
+    // Start and goal only need to be free floor; everything in between needs full clearance.
+    let passable = |cell: IVec2| {
+        let center = map.cell_center(cell).xz();
+        cell == start_cell
+            || cell == goal_cell
+            || (map.has_clearance(cell, clearance)
+                && avoid
+                    .iter()
+                    .all(|(point, radius)| center.distance(point.xz()) >= *radius))
+    };
+
+    let cells = astar(map, start_cell, goal_cell, &passable)?;
+
+    let mut points = Vec::with_capacity(cells.len() + 2);
+    points.push(start);
+    // Interior cells only; start and goal share a cell when the move stays within one.
+    for &cell in cells.iter().skip(1).take(cells.len().saturating_sub(2)) {
+        let center = map.cell_center(cell);
+        points.push(Vec3::new(center.x, start.y, center.z));
+    }
+    points.push(Vec3::new(goal.x, start.y, goal.z));
+
+    Some(smooth_path(map, &points, &passable))
+}
+
+/// Total length of a polyline.
+pub fn path_length(points: &[Vec3]) -> f32 {
+    points.windows(2).map(|w| w[0].distance(w[1])).sum()
+}
+
+/// Grid A* with an octile-distance heuristic; returns the cell chain including both ends.
+fn astar(
+    map: &WarehouseMap,
+    start: IVec2,
+    goal: IVec2,
+    passable: &impl Fn(IVec2) -> bool,
+) -> Option<Vec<IVec2>> {
+    let mut best_cost = vec![u32::MAX; map.cell_count()];
+    let mut came_from: Vec<Option<IVec2>> = vec![None; map.cell_count()];
+    let mut open = BinaryHeap::new();
+
+    best_cost[map.index(start)] = 0;
+    open.push(Reverse((octile(start, goal), 0u32, start.x, start.y)));
+
+    while let Some(Reverse((_, cost, x, y))) = open.pop() {
+        let cell = IVec2::new(x, y);
+        if cell == goal {
+            let mut chain = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from[map.index(current)] {
+                chain.push(previous);
+                current = previous;
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+        if cost > best_cost[map.index(cell)] {
+            // Stale heap entry superseded by a cheaper route.
+            continue;
+        }
+
+        for offset in NEIGHBOURS {
+            let next = cell + offset;
+            if !map.in_bounds(next) || !passable(next) {
+                continue;
+            }
+            let diagonal = offset.x != 0 && offset.y != 0;
+            if diagonal
+                && (!passable(cell + IVec2::new(offset.x, 0))
+                    || !passable(cell + IVec2::new(0, offset.y)))
+            {
+                continue;
+            }
+
+            let next_cost = cost
+                + if diagonal {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+            let index = map.index(next);
+            if next_cost < best_cost[index] {
+                best_cost[index] = next_cost;
+                came_from[index] = Some(cell);
+                open.push(Reverse((
+                    next_cost + octile(next, goal),
+                    next_cost,
+                    next.x,
+                    next.y,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// Admissible 8-connected distance estimate in step-cost units.
+fn octile(a: IVec2, b: IVec2) -> u32 {
+    let d = (a - b).abs();
+    let (long, short) = (d.x.max(d.y) as u32, d.x.min(d.y) as u32);
+    STRAIGHT_COST * (long - short) + DIAGONAL_COST * short
+}
+
+/// Drops intermediate waypoints whenever the straight segment between their neighbours
+/// stays on passable cells.
+fn smooth_path(
+    map: &WarehouseMap,
+    points: &[Vec3],
+    passable: &impl Fn(IVec2) -> bool,
+) -> Vec<Vec3> {
+    if points.len() <= 2 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+    let mut anchor = 0;
+    while anchor < points.len() - 1 {
+        // Furthest point still visible from the anchor; the next point is always visible.
+        let mut next = anchor + 1;
+        for candidate in (anchor + 2..points.len()).rev() {
+            if line_of_sight(map, points[anchor], points[candidate], passable) {
+                next = candidate;
+                break;
+            }
+        }
+        smoothed.push(points[next]);
+        anchor = next;
+    }
+    smoothed
+}
+
+/// Samples the segment at quarter-cell spacing and checks every touched cell is passable,
+/// then walks it cell by cell so that no blocked cell slips between the samples.
+fn line_of_sight(map: &WarehouseMap, a: Vec3, b: Vec3, passable: &impl Fn(IVec2) -> bool) -> bool {
+    let step = map.cell_size / 4.0;
+    let samples = (a.distance(b) / step).ceil().max(1.0) as usize;
+    (0..=samples).all(|i| {
+        let point = a.lerp(b, i as f32 / samples as f32);
+        map.world_to_cell(point).is_some_and(passable)
+    }) && crosses_no_blocked_cell(map, a, b)
+}
+
+/// Walks every cell the segment passes through and checks none is blocked. Where the
+/// segment runs exactly through a cell corner, both cells beside the corner must be free
+/// too, as for a diagonal A* step.
+fn crosses_no_blocked_cell(map: &WarehouseMap, a: Vec3, b: Vec3) -> bool {
+    let (Some(mut cell), Some(end)) = (map.world_to_cell(a), map.world_to_cell(b)) else {
+        return false;
+    };
+    let free = |cell: IVec2| map.in_bounds(cell) && !map.is_blocked(cell);
+    let from = (a.xz() - map.origin) / map.cell_size;
+    let delta = (b.xz() - map.origin) / map.cell_size - from;
+    let step = IVec2::new(direction(delta.x), direction(delta.y));
+    // Fraction of the segment at which it crosses the next cell border on each axis, and
+    // the fraction one whole cell takes.
+    let crossing = |axis: usize| match step[axis] {
+        1 => (cell[axis] as f32 + 1.0 - from[axis]) / delta[axis],
+        -1 => (from[axis] - cell[axis] as f32) / -delta[axis],
+        _ => f32::INFINITY,
+    };
+    let mut next = Vec2::new(crossing(0), crossing(1));
+    let across = Vec2::new(1.0 / delta.x.abs(), 1.0 / delta.y.abs());
+
+    let mut remaining = (end - cell).abs().element_sum();
+    while remaining > 0 {
+        if !free(cell) {
+            return false;
+        }
+        if next.x < next.y {
+            cell.x += step.x;
+            next.x += across.x;
+            remaining -= 1;
+        } else if next.y < next.x {
+            cell.y += step.y;
+            next.y += across.y;
+            remaining -= 1;
+        } else {
+            if !free(cell + IVec2::new(step.x, 0)) || !free(cell + IVec2::new(0, step.y)) {
+                return false;
+            }
+            cell += step;
+            next += across;
+            remaining -= 2;
+        }
+    }
+    free(cell) && free(end)
+}
+
+/// Sign of a grid step along one axis: -1, 0 or 1.
+fn direction(delta: f32) -> i32 {
+    if delta > 0.0 {
+        1
+    } else if delta < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CellKind, Obstacle};
+    use crate::model::{Scenario, ScenarioConfig, ScenarioLibrary, SimRng};
+
+    /// A 10×10 arena with half-unit cells and the given walls.
+    fn map(walls: &[(Vec2, Vec2)]) -> WarehouseMap {
+        let config = ScenarioConfig {
+            arena_size: 10.0,
+            cell_size: 0.5,
+            obstacles: walls
+                .iter()
+                .map(|&(min, max)| Obstacle::new(CellKind::Wall, min, max))
+                .collect(),
+            ..ScenarioConfig::build(Scenario::Small, 0, &ScenarioLibrary::default())
+        };
+        WarehouseMap::from_config(&config)
+    }
+
+    /// Checks every segment of `path` at a fine spacing for blocked cells.
+    fn assert_clear(map: &WarehouseMap, path: &[Vec3]) {
+        for segment in path.windows(2) {
+            let samples = (segment[0].distance(segment[1]) / 0.02).ceil() as usize;
+            for i in 0..=samples {
+                let point = segment[0].lerp(segment[1], i as f32 / samples.max(1) as f32);
+                assert!(!map.is_blocked_at(point), "{point} on {path:?} is blocked");
+            }
+        }
+    }
+
+    #[test]
+    fn paths_go_around_obstacles() {
+        let map = map(&[(Vec2::new(-0.5, -3.0), Vec2::new(0.5, 3.0))]);
+        let (start, goal) = (Vec3::new(-3.0, 0.5, 0.0), Vec3::new(3.0, 0.5, 0.0));
+        let path = plan_path(&map, start, goal, 0.4).expect("a way around the wall");
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.len() > 2);
+        assert!(path_length(&path) > start.distance(goal) + 1.0);
+        assert_clear(&map, &path);
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        // A closed box around the goal.
+        let map = map(&[
+            (Vec2::new(1.0, -2.0), Vec2::new(4.0, -1.5)),
+            (Vec2::new(1.0, 1.5), Vec2::new(4.0, 2.0)),
+            (Vec2::new(1.0, -2.0), Vec2::new(1.5, 2.0)),
+            (Vec2::new(3.5, -2.0), Vec2::new(4.0, 2.0)),
+        ]);
+        let start = Vec3::new(-3.0, 0.5, 0.0);
+        assert_eq!(plan_path(&map, start, Vec3::new(2.5, 0.5, 0.0), 0.2), None);
+        // Goals on a wall or off the grid are turned down too.
+        assert_eq!(plan_path(&map, start, Vec3::new(1.2, 0.5, 0.0), 0.2), None);
+        assert_eq!(plan_path(&map, start, Vec3::new(9.0, 0.5, 0.0), 0.2), None);
+    }
+
+    #[test]
+    fn smoothed_paths_never_cross_blocked_cells() {
+        for seed in 0..500 {
+            let mut rng = SimRng::new(seed);
+            let walls: Vec<(Vec2, Vec2)> = (0..6)
+                .map(|_| {
+                    let min = Vec2::new(rng.range(-4.0, 3.0), rng.range(-4.0, 3.0));
+                    (
+                        min,
+                        min + Vec2::new(rng.range(0.3, 2.0), rng.range(0.3, 2.0)),
+                    )
+                })
+                .collect();
+            let map = map(&walls);
+            let mut free_point = || loop {
+                let point = Vec3::new(rng.range(-4.5, 4.5), 0.5, rng.range(-4.5, 4.5));
+                if !map.is_blocked_at(point) {
+                    return point;
+                }
+            };
+            let (start, goal) = (free_point(), free_point());
+            if let Some(path) = plan_path(&map, start, goal, 0.0) {
+                assert_clear(&map, &path);
+            }
+        }
+    }
+}
@@ -0,0 +1,802 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::map::WarehouseMap;
+use crate::pathfinding::NEIGHBOURS;
+
+/// Shortest time any leg of a job takes, so its events never share a timestamp.
+pub const MIN_LEG_TIME: f64 = 0.5;
+
+/// Extra gap kept between reserved footprints, so rounding never lets two robots touch.
+pub const CLEARANCE_MARGIN: f32 = 0.05;
+/// How long a robot holds back before retrying a move that would cross a reservation.
+const DEPART_STEP: f64 = 0.1;
+/// Departure retries per move before the move is given up on.
+const MAX_DEPART_RETRIES: usize = 100;
+/// Search nodes expanded per leg before the robot gives up and tries again later.
+const MAX_EXPANSIONS: usize = 20_000;
+
+/// How robot routes are planned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannerKind {
+    /// Every robot takes its shortest route and ignores the others; `detect_conflicts`
+    /// reports where they meet.
+    #[default]
+    Independent,
+    /// Robots reserve their timed routes in a space-time table, and later robots plan
+    /// around or wait for earlier reservations (prioritized planning).
+    Reservation,
+    /// Robots starting jobs together are routed jointly by Conflict-Based Search, optimal
+    /// for the batch; each batch then reserves its routes like `Reservation`.
+    Cbs,
+    /// Bounded-suboptimal Enhanced CBS, trading path cost for a faster joint search.
+    Ecbs,
+}
+
+impl PlannerKind {
+    /// Display name used in the headless report.
+    pub fn label(self) -> &'static str {
+        match self {
+            PlannerKind::Independent => "Independent",
+            PlannerKind::Reservation => "Reservation",
+            PlannerKind::Cbs => "CBS",
+            PlannerKind::Ecbs => "ECBS",
+        }
+    }
+
+    /// Parses a planner name as given on the command line (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "independent" => Some(PlannerKind::Independent),
+            "reservation" | "reserved" => Some(PlannerKind::Reservation),
+            "cbs" => Some(PlannerKind::Cbs),
+            "ecbs" => Some(PlannerKind::Ecbs),
+            _ => None,
+        }
+    }
+}
+
+/// ECBS suboptimality bound used unless `--ecbs-weight` says otherwise.
+pub const DEFAULT_ECBS_WEIGHT: f32 = 1.5;
+
+#[derive(Resource, Clone, Copy)]
+/// Planner used for every route planned from now on.
+pub struct ActivePlanner {
+    /// Planner used for new routes; scenario files may switch it when loaded.
+    pub kind: PlannerKind,
+    /// ECBS returns joint plans costing at most this factor times the optimum (≥ 1).
+    pub ecbs_weight: f32,
+}
+
+impl ActivePlanner {
+    /// Selects `kind`, with `ecbs_weight` as the bound should it be ECBS.
+    pub fn new(kind: PlannerKind, ecbs_weight: f32) -> Self {
+        Self { kind, ecbs_weight }
+    }
+}
+
+/// A robot's claimed trajectory. The robot stands at the first point until the route
+/// starts and stays parked at the last point afterwards.
+#[derive(Clone, Debug)]
+struct Reservation {
+    radius: f32,
+    route: Vec<(f64, Vec2)>,
+}
+
+impl Reservation {
+    /// Where the robot is at sim time `t`.
+    fn position_at(&self, t: f64) -> Vec2 {
+        let next = self.route.partition_point(|&(time, _)| time <= t);
+        if next == 0 {
+            return self.route[0].1;
+        }
+        if next == self.route.len() {
+            return self.route[next - 1].1;
+        }
+        let (t0, p0) = self.route[next - 1];
+        let (t1, p1) = self.route[next];
+        p0.lerp(p1, ((t - t0) / (t1 - t0)) as f32)
+    }
+
+    /// Adds the time spans in which the trajectory comes closer than `distance` to `point`.
+    fn blocked_spans(&self, point: Vec2, distance: f32, spans: &mut Vec<(f64, f64)>) {
+        let (first_time, first) = self.route[0];
+        if first.distance(point) < distance {
+            spans.push((f64::NEG_INFINITY, first_time));
+        }
+        for pair in self.route.windows(2) {
+            let ((t0, p0), (t1, p1)) = (pair[0], pair[1]);
+            let duration = t1 - t0;
+            if duration <= 0.0 {
+                continue;
+            }
+            // |p0 - point + v·s|² < distance² for s in [0, duration].
+            let velocity = (p1 - p0) / duration as f32;
+            let offset = p0 - point;
+            let a = velocity.length_squared() as f64;
+            let b = 2.0 * offset.dot(velocity) as f64;
+            let c = (offset.length_squared() - distance * distance) as f64;
+            if a < 1e-12 {
+                if c < 0.0 {
+                    spans.push((t0, t1));
+                }
+                continue;
+            }
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant <= 0.0 {
+                continue;
+            }
+            let root = discriminant.sqrt();
+            let enter = ((-b - root) / (2.0 * a)).max(0.0);
+            let leave = ((-b + root) / (2.0 * a)).min(duration);
+            if enter < leave {
+                spans.push((t0 + enter, t0 + leave));
+            }
+        }
+        let (last_time, last) = self.route[self.route.len() - 1];
+        if last.distance(point) < distance {
+            spans.push((last_time, f64::INFINITY));
+        }
+    }
+
+    /// True if a robot driving straight from `from` at `depart` to `to` at `arrive` stays at
+    /// least `distance` away from this trajectory the whole time.
+    fn keeps_clear(&self, from: Vec2, to: Vec2, depart: f64, arrive: f64, distance: f32) -> bool {
+        let mover = |t: f64| {
+            if arrive <= depart {
+                to
+            } else {
+                from.lerp(to, ((t - depart) / (arrive - depart)) as f32)
+            }
+        };
+        // Both robots move linearly between these times, so does their offset.
+        let mut times = vec![depart];
+        times.extend(
+            self.route
+                .iter()
+                .map(|&(t, _)| t)
+                .filter(|&t| t > depart && t < arrive),
+        );
+        times.push(arrive);
+
+        times.windows(2).all(|pair| {
+            let start = mover(pair[0]) - self.position_at(pair[0]);
+            let end = mover(pair[1]) - self.position_at(pair[1]);
+            closest_to_origin(start, end) >= distance
+        })
+    }
+}
+
+/// True if a robot of `point_radius` standing at `point` is in the way of a robot of `radius`
+/// driving along `route`.
+pub fn blocks_route(point: Vec2, point_radius: f32, route: &[Vec3], radius: f32) -> bool {
+    let reach = point_radius + radius + CLEARANCE_MARGIN;
+    match route {
+        [] => false,
+        [only] => only.xz().distance(point) < reach,
+        _ => route
+            .windows(2)
+            .any(|pair| closest_to_origin(pair[0].xz() - point, pair[1].xz() - point) < reach),
+    }
+}
+
+/// Distance from the origin to the segment `a`–`b`.
+pub fn closest_to_origin(a: Vec2, b: Vec2) -> f32 {
+    let along = b - a;
+    let length_squared = along.length_squared();
+    if length_squared < 1e-12 {
+        return a.length();
+    }
+    let s = (-a.dot(along) / length_squared).clamp(0.0, 1.0);
+    (a + along * s).length()
+}
+
+#[derive(Resource, Default)]
+/// Space-time reservations of every robot's route, keyed by robot id.
+///
+/// Routes are claimed when their `DepartRobot` and `MoveRobot` events are scheduled and
+/// replaced by the robot's next route. A robot that is not moving holds its spot.
+pub struct ReservationTable {
+    reservations: HashMap<usize, Reservation>,
+}
+
+impl ReservationTable {
+    /// Claims `position` for the robot from now on.
+    pub fn park(&mut self, robot_id: usize, radius: f32, position: Vec3) {
+        self.reserve(robot_id, radius, &[(0.0, position)]);
+    }
+
+    /// Claims a timed route for the robot, replacing its previous reservation.
+    pub fn reserve(&mut self, robot_id: usize, radius: f32, route: &[(f64, Vec3)]) {
+        if route.is_empty() {
+            return;
+        }
+        let route = route.iter().map(|&(t, point)| (t, point.xz())).collect();
+        self.reservations
+            .insert(robot_id, Reservation { radius, route });
+    }
+
+    /// Robots other than `robot_id` that have stopped for good by `now` close enough to
+    /// `route` to block a robot of `radius` driving along it, in id order.
+    pub fn parked_along(
+        &self,
+        robot_id: usize,
+        route: &[Vec3],
+        radius: f32,
+        now: f64,
+    ) -> Vec<usize> {
+        let mut parked: Vec<usize> = self
+            .reservations
+            .iter()
+            .filter(|&(&id, reservation)| {
+                let (end, last) = reservation.route[reservation.route.len() - 1];
+                id != robot_id
+                    && end <= now
+                    && blocks_route(last, reservation.radius, route, radius)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        parked.sort_unstable();
+        parked
+    }
+
+    /// Sorted, disjoint spans from `from` on during which `point` is free for a robot of
+    /// `radius`, disregarding the reservations of the robots in `ignore`.
+    pub fn safe_intervals(
+        &self,
+        ignore: &[usize],
+        radius: f32,
+        point: Vec2,
+        from: f64,
+    ) -> Vec<(f64, f64)> {
+        let mut blocked = Vec::new();
+        for (id, reservation) in &self.reservations {
+            if !ignore.contains(id) {
+                let distance = radius + reservation.radius + CLEARANCE_MARGIN;
+                reservation.blocked_spans(point, distance, &mut blocked);
+            }
+        }
+        blocked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut safe = Vec::new();
+        let mut cursor = from;
+        for (start, end) in blocked {
+            if end <= cursor {
+                continue;
+            }
+            if start > cursor {
+                safe.push((cursor, start));
+            }
+            cursor = end;
+        }
+        if cursor < f64::INFINITY {
+            safe.push((cursor, f64::INFINITY));
+        }
+        safe
+    }
+
+    /// True if a robot of `radius` can drive straight between two points in the given time
+    /// window, disregarding the reservations of the robots in `ignore`.
+    pub fn move_is_clear(
+        &self,
+        ignore: &[usize],
+        radius: f32,
+        from: Vec2,
+        to: Vec2,
+        depart: f64,
+        arrive: f64,
+    ) -> bool {
+        self.reservations.iter().all(|(id, reservation)| {
+            ignore.contains(id)
+                || reservation.keeps_clear(
+                    from,
+                    to,
+                    depart,
+                    arrive,
+                    radius + reservation.radius + CLEARANCE_MARGIN,
+                )
+        })
+    }
+}
+
+/// Plans timed legs through `stops` with Safe Interval Path Planning (SIPP) against the
+/// routes already reserved by other robots.
+///
+/// Each stop is a position and the time to stay there; the robot must be able to stand at
+/// the last stop indefinitely. Moves run at `speed` between grid cell centres that keep
+/// `radius` from obstacles, with the same no-corner-cutting rule as `plan_path`, and may
+/// wait in place while a cell is still taken. Robots drive at constant speed here, so
+/// types with an acceleration are timed as if they had none. Every leg lasts at least
+/// `MIN_LEG_TIME`. Returns one timed route per stop, each starting where the previous one
+/// ended, or `None` if any leg cannot be planned yet.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_reserved(
+    table: &ReservationTable,
+    map: &WarehouseMap,
+    robot_id: usize,
+    radius: f32,
+    speed: f32,
+    start: Vec3,
+    now: f64,
+    stops: &[(Vec3, f64)],
+) -> Option<Vec<Vec<(f64, Vec3)>>> {
+    let mut search = LegSearch {
+        table,
+        map,
+        robot_id,
+        radius,
+        speed,
+        height: start.y,
+        clearance: HashMap::new(),
+    };
+    let mut legs = Vec::with_capacity(stops.len());
+    let (mut from, mut depart) = (start, now);
+    for (index, &(goal, dwell)) in stops.iter().enumerate() {
+        let last = index + 1 == stops.len();
+        let leg = search.leg(from, depart, goal, if last { f64::INFINITY } else { dwell })?;
+        depart = leg[leg.len() - 1].0 + dwell;
+        from = goal;
+        legs.push(leg);
+    }
+    Some(legs)
+}
+
+/// Shared state of the searches for one robot's legs.
+struct LegSearch<'a> {
+    table: &'a ReservationTable,
+    map: &'a WarehouseMap,
+    robot_id: usize,
+    radius: f32,
+    speed: f32,
+    height: f32,
+    /// Cached `has_clearance` results by cell.
+    clearance: HashMap<IVec2, bool>,
+}
+
+/// A search node: a cell, one of its safe intervals, and the earliest arrival there.
+#[derive(Clone, Copy)]
+struct Visit {
+    arrival: f64,
+    /// Previous node and when the robot left it.
+    parent: Option<((IVec2, usize), f64)>,
+}
+
+/// Open-list entry ordered by estimated arrival at the goal, earliest first.
+struct Frontier {
+    estimate: f64,
+    arrival: f64,
+    node: (IVec2, usize),
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(other.arrival.total_cmp(&self.arrival))
+            .then((other.node.0.x, other.node.0.y, other.node.1).cmp(&(
+                self.node.0.x,
+                self.node.0.y,
+                self.node.1,
+            )))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Frontier {}
+
+impl LegSearch<'_> {
+    /// Timed route from `start` at `depart` to `goal`, arriving in a safe interval that lets
+    /// the robot stay for `dwell` seconds.
+    fn leg(
+        &mut self,
+        start: Vec3,
+        depart: f64,
+        goal: Vec3,
+        dwell: f64,
+    ) -> Option<Vec<(f64, Vec3)>> {
+        let start_cell = self.map.world_to_cell(start)?;
+        let goal_cell = self.map.world_to_cell(goal)?;
+        let (map, height, speed) = (self.map, self.height, self.speed);
+        let goal = Vec3::new(goal.x, height, goal.z);
+        let earliest_end = depart + MIN_LEG_TIME;
+        let fits = |arrival: f64, (_, end): (f64, f64)| {
+            let settled = arrival.max(earliest_end);
+            settled < end && (end == f64::INFINITY || end >= settled + dwell)
+        };
+
+        if start_cell == goal_cell {
+            return self.direct_leg(start, depart, goal, &fits);
+        }
+
+        let position = |cell: IVec2| {
+            if cell == start_cell {
+                start
+            } else if cell == goal_cell {
+                goal
+            } else {
+                let center = map.cell_center(cell);
+                Vec3::new(center.x, height, center.z)
+            }
+        };
+
+        let mut intervals: HashMap<IVec2, Vec<(f64, f64)>> = HashMap::new();
+        // Nothing to search for if the goal is never free for long enough.
+        let goal_intervals = self.intervals(&mut intervals, goal_cell, goal, depart);
+        if !goal_intervals
+            .iter()
+            .any(|&(from, to)| fits(from.max(depart), (from, to)))
+        {
+            return None;
+        }
+        let start_intervals =
+            self.intervals(&mut intervals, start_cell, position(start_cell), depart);
+        let first = start_intervals
+            .iter()
+            .position(|&(s, e)| s <= depart && depart < e)?;
+
+        let mut visits: HashMap<(IVec2, usize), Visit> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        let heuristic = |point: Vec3| (point.distance(goal) / speed) as f64;
+        visits.insert(
+            (start_cell, first),
+            Visit {
+                arrival: depart,
+                parent: None,
+            },
+        );
+        open.push(Frontier {
+            estimate: depart + heuristic(start),
+            arrival: depart,
+            node: (start_cell, first),
+        });
+
+        let mut expansions = 0;
+        while let Some(Frontier { arrival, node, .. }) = open.pop() {
+            if arrival > visits[&node].arrival {
+                continue;
+            }
+            let (cell, index) = node;
+            let interval = intervals[&cell][index];
+            if cell == goal_cell && fits(arrival, interval) {
+                return Some(self.trace(&visits, node, start, depart, earliest_end, &position));
+            }
+            expansions += 1;
+            if expansions > MAX_EXPANSIONS {
+                return None;
+            }
+
+            let here = position(cell);
+            for offset in NEIGHBOURS {
+                let next = cell + offset;
+                let passable = |search: &mut Self, c: IVec2| {
+                    c == start_cell || c == goal_cell || search.has_clearance(c)
+                };
+                if !self.map.in_bounds(next) || !passable(self, next) {
+                    continue;
+                }
+                let diagonal = offset.x != 0 && offset.y != 0;
+                if diagonal
+                    && (!passable(self, cell + IVec2::new(offset.x, 0))
+                        || !passable(self, cell + IVec2::new(0, offset.y)))
+                {
+                    continue;
+                }
+
+                let there = position(next);
+                let duration = (here.distance(there) / self.speed) as f64;
+                let next_intervals = self.intervals(&mut intervals, next, there, depart);
+                for (next_index, &(safe_from, safe_to)) in next_intervals.iter().enumerate() {
+                    if safe_from - duration > interval.1 {
+                        break;
+                    }
+                    let Some(leave) = self.earliest_move(
+                        here,
+                        there,
+                        arrival.max(safe_from - duration),
+                        duration,
+                        interval.1,
+                        safe_to,
+                    ) else {
+                        continue;
+                    };
+                    let reached = leave + duration;
+                    let key = (next, next_index);
+                    if visits
+                        .get(&key)
+                        .is_some_and(|visit| visit.arrival <= reached)
+                    {
+                        continue;
+                    }
+                    visits.insert(
+                        key,
+                        Visit {
+                            arrival: reached,
+                            parent: Some((node, leave)),
+                        },
+                    );
+                    open.push(Frontier {
+                        estimate: reached + heuristic(there),
+                        arrival: reached,
+                        node: key,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Moves straight to a goal in the start cell, waiting until the move is clear.
+    fn direct_leg(
+        &mut self,
+        start: Vec3,
+        depart: f64,
+        goal: Vec3,
+        fits: &impl Fn(f64, (f64, f64)) -> bool,
+    ) -> Option<Vec<(f64, Vec3)>> {
+        let standing = self
+            .table
+            .safe_intervals(&[self.robot_id], self.radius, start.xz(), depart);
+        let (_, stay_until) = *standing.iter().find(|&&(s, e)| s <= depart && depart < e)?;
+        let arriving = self
+            .table
+            .safe_intervals(&[self.robot_id], self.radius, goal.xz(), depart);
+        let duration = (start.distance(goal) / self.speed) as f64;
+
+        for &(safe_from, safe_to) in &arriving {
+            let Some(leave) = self.earliest_move(
+                start,
+                goal,
+                depart.max(safe_from - duration),
+                duration,
+                stay_until,
+                safe_to,
+            ) else {
+                continue;
+            };
+            let arrival = leave + duration;
+            if !fits(arrival, (safe_from, safe_to)) {
+                continue;
+            }
+            let mut route = vec![(depart, start)];
+            if leave > depart {
+                route.push((leave, start));
+            }
+            route.push((arrival.max(depart + MIN_LEG_TIME), goal));
+            return Some(route);
+        }
+        None
+    }
+
+    /// Earliest departure from `leave` on that drives `from` → `to` without crossing a
+    /// reservation, leaving by `stay_until` and arriving before `arrive_by`.
+    fn earliest_move(
+        &self,
+        from: Vec3,
+        to: Vec3,
+        leave: f64,
+        duration: f64,
+        stay_until: f64,
+        arrive_by: f64,
+    ) -> Option<f64> {
+        let mut leave = leave;
+        for _ in 0..MAX_DEPART_RETRIES {
+            if leave > stay_until || leave + duration >= arrive_by {
+                return None;
+            }
+            if self.table.move_is_clear(
+                &[self.robot_id],
+                self.radius,
+                from.xz(),
+                to.xz(),
+                leave,
+                leave + duration,
+            ) {
+                return Some(leave);
+            }
+            leave += DEPART_STEP;
+        }
+        None
+    }
+
+    /// Safe intervals of a cell, computed once per leg.
+    fn intervals<'m>(
+        &self,
+        cache: &'m mut HashMap<IVec2, Vec<(f64, f64)>>,
+        cell: IVec2,
+        point: Vec3,
+        from: f64,
+    ) -> &'m Vec<(f64, f64)> {
+        cache.entry(cell).or_insert_with(|| {
+            self.table
+                .safe_intervals(&[self.robot_id], self.radius, point.xz(), from)
+        })
+    }
+
+    fn has_clearance(&mut self, cell: IVec2) -> bool {
+        let (map, radius) = (self.map, self.radius);
+        *self
+            .clearance
+            .entry(cell)
+            .or_insert_with(|| map.has_clearance(cell, radius))
+    }
+
+    /// Rebuilds the timed route ending at `node`, with a wait point wherever the robot
+    /// held back before a move.
+    fn trace(
+        &self,
+        visits: &HashMap<(IVec2, usize), Visit>,
+        node: (IVec2, usize),
+        start: Vec3,
+        depart: f64,
+        earliest_end: f64,
+        position: &impl Fn(IVec2) -> Vec3,
+    ) -> Vec<(f64, Vec3)> {
+        let mut moves = Vec::new();
+        let mut current = node;
+        while let Some((previous, leave)) = visits[&current].parent {
+            moves.push((previous.0, leave, current.0, visits[&current].arrival));
+            current = previous;
+        }
+        moves.reverse();
+
+        let mut route = vec![(depart, start)];
+        for (from, leave, to, arrival) in moves {
+            if leave > route[route.len() - 1].0 {
+                route.push((leave, position(from)));
+            }
+            route.push((arrival, position(to)));
+        }
+        let (arrival, goal) = route[route.len() - 1];
+        if arrival < earliest_end {
+            route.push((earliest_end, goal));
+        }
+        route
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CellKind, Obstacle};
+    use crate::model::{Scenario, ScenarioConfig, ScenarioLibrary};
+
+    /// Asserts two interval lists agree to within rounding.
+    fn assert_spans(actual: &[(f64, f64)], expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} vs {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            let close = |x: f64, y: f64| x == y || (x - y).abs() < 1e-3;
+            assert!(
+                close(a.0, e.0) && close(a.1, e.1),
+                "{actual:?} vs {expected:?}"
+            );
+        }
+    }
+
+    /// Robot 1, radius 0.3, driving along the x axis from -5 to 5 between t = 0 and 10.
+    fn crossing_table() -> ReservationTable {
+        let mut table = ReservationTable::default();
+        let route = [
+            (0.0, Vec3::new(-5.0, 0.0, 0.0)),
+            (10.0, Vec3::new(5.0, 0.0, 0.0)),
+        ];
+        table.reserve(1, 0.3, &route);
+        table
+    }
+
+    #[test]
+    fn reserved_routes_split_safe_intervals() {
+        let table = crossing_table();
+        // A robot of radius 0.2 at the origin needs 0.3 + 0.2 + margin = 0.55 of room, which
+        // the reserved robot takes from t = 4.45 to 5.55.
+        let safe = table.safe_intervals(&[], 0.2, Vec2::ZERO, 0.0);
+        assert_spans(&safe, &[(0.0, 4.45), (5.55, f64::INFINITY)]);
+        let safe = table.safe_intervals(&[], 0.2, Vec2::ZERO, 5.0);
+        assert_spans(&safe, &[(5.55, f64::INFINITY)]);
+        // Away from the route, and for the robot itself, the point is always free.
+        let safe = table.safe_intervals(&[], 0.2, Vec2::new(0.0, 2.0), 0.0);
+        assert_spans(&safe, &[(0.0, f64::INFINITY)]);
+        let safe = table.safe_intervals(&[1], 0.2, Vec2::ZERO, 0.0);
+        assert_spans(&safe, &[(0.0, f64::INFINITY)]);
+    }
+
+    #[test]
+    fn finished_routes_only_hold_their_last_spot() {
+        let mut table = crossing_table();
+        // Once the robot has passed, the cells it drove through are free again; only the
+        // spot it stopped on stays taken, from shortly before it arrives.
+        let safe = table.safe_intervals(&[], 0.2, Vec2::ZERO, 6.0);
+        assert_spans(&safe, &[(6.0, f64::INFINITY)]);
+        let safe = table.safe_intervals(&[], 0.2, Vec2::new(5.0, 0.0), 0.0);
+        assert_spans(&safe, &[(0.0, 9.45)]);
+        assert_eq!(
+            table.parked_along(
+                0,
+                &[Vec3::new(5.0, 0.0, -2.0), Vec3::new(5.0, 0.0, 2.0)],
+                0.2,
+                12.0
+            ),
+            [1]
+        );
+
+        // Its next route replaces the old reservation, spot included.
+        table.park(1, 0.3, Vec3::new(-3.0, 0.0, 3.0));
+        let safe = table.safe_intervals(&[], 0.2, Vec2::new(5.0, 0.0), 0.0);
+        assert_spans(&safe, &[(0.0, f64::INFINITY)]);
+        let safe = table.safe_intervals(&[], 0.2, Vec2::ZERO, 0.0);
+        assert_spans(&safe, &[(0.0, f64::INFINITY)]);
+    }
+
+    #[test]
+    fn planned_legs_wait_for_crossing_robots() {
+        // A one-cell-wide east-west corridor along z = 0.25 with a north-south gap at x = 0.
+        let walls = [
+            (Vec2::new(-5.0, -5.0), Vec2::new(-0.5, 0.0)),
+            (Vec2::new(0.5, -5.0), Vec2::new(5.0, 0.0)),
+            (Vec2::new(-5.0, 0.5), Vec2::new(-0.5, 5.0)),
+            (Vec2::new(0.5, 0.5), Vec2::new(5.0, 5.0)),
+        ];
+        let config = ScenarioConfig {
+            arena_size: 10.0,
+            cell_size: 0.5,
+            obstacles: walls
+                .iter()
+                .map(|&(min, max)| Obstacle::new(CellKind::Wall, min, max))
+                .collect(),
+            ..ScenarioConfig::build(Scenario::Small, 0, &ScenarioLibrary::default())
+        };
+        let map = WarehouseMap::from_config(&config);
+
+        // Robot 1 drives through the gap, reaching the corridor just as robot 0 would.
+        let mut table = ReservationTable::default();
+        let crossing = [
+            (0.0, Vec3::new(0.25, 0.0, -3.0)),
+            (6.0, Vec3::new(0.25, 0.0, 3.5)),
+        ];
+        table.reserve(1, 0.2, &crossing);
+
+        let (start, goal) = (Vec3::new(-3.0, 0.0, 0.25), Vec3::new(3.0, 0.0, 0.25));
+        let legs = plan_reserved(&table, &map, 0, 0.2, 1.0, start, 0.0, &[(goal, 0.0)])
+            .expect("a plan that waits for robot 1");
+        assert_eq!(legs.len(), 1);
+        let route = &legs[0];
+        assert_eq!(route[0], (0.0, start));
+        assert_eq!(route[route.len() - 1].1, goal);
+        // Driving straight through would take 6 s; instead the robot holds back before the
+        // gap while robot 1 goes by.
+        assert!(route[route.len() - 1].0 > 6.0, "{route:?}");
+        let waits = route
+            .windows(2)
+            .filter(|pair| pair[0].1 == pair[1].1)
+            .count();
+        assert_eq!(waits, 1, "{route:?}");
+
+        let mover = Reservation {
+            radius: 0.2,
+            route: route.iter().map(|&(t, p)| (t, p.xz())).collect(),
+        };
+        let other = Reservation {
+            radius: 0.2,
+            route: crossing.iter().map(|&(t, p)| (t, p.xz())).collect(),
+        };
+        for step in 0..=1200 {
+            let t = step as f64 * 0.01;
+            let gap = mover.position_at(t).distance(other.position_at(t));
+            assert!(gap >= 0.4, "robots {gap} apart at t = {t} on {route:?}");
+        }
+    }
+}
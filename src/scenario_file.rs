@@ -0,0 +1,1146 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::allocation::AllocatorKind;
+use crate::arrivals::{ArrivalProcess, TaskTemplate};
+use crate::map::{CellKind, DEFAULT_CELL_SIZE, Obstacle};
+use crate::model::{
+    BatteryConfig, DEFAULT_ARENA_SIZE, DEFAULT_COLLISION_RADIUS, DEFAULT_LOAD, DEFAULT_ROBOT_SPEED,
+    RobotShape, RobotSpec, RobotType, ScenarioConfig, ScenarioDefinition, TaskSpec,
+};
+use crate::reservation::PlannerKind;
+
+/// Directory scanned for scenario files at startup.
+pub const SCENARIO_DIR: &str = "scenarios";
+
+/// On-disk scenario description, shared by the TOML and JSON formats.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    /// Display name; defaults to the file stem.
+    name: Option<String>,
+    #[serde(default = "default_arena_size")]
+    arena_size: f32,
+    /// Speed of the `standard` robot type.
+    #[serde(default = "default_robot_speed")]
+    robot_speed: f32,
+    /// Footprint radius of the `standard` robot type.
+    #[serde(default = "default_collision_radius")]
+    collision_radius: f32,
+    #[serde(default = "default_cell_size")]
+    cell_size: f32,
+    /// Robot types besides `standard`; an entry named `standard` replaces it.
+    #[serde(default)]
+    robot_types: Vec<RobotTypeEntry>,
+    robots: Vec<RobotEntry>,
+    #[serde(default)]
+    tasks: Vec<TaskEntry>,
+    #[serde(default)]
+    obstacles: Vec<ObstacleEntry>,
+    /// Allocation strategy selected when this scenario is loaded.
+    allocator: Option<AllocatorKind>,
+    /// Route planner selected when this scenario is loaded.
+    planner: Option<PlannerKind>,
+    /// Processes releasing more tasks during the run.
+    #[serde(default)]
+    arrivals: Vec<ArrivalEntry>,
+    /// Enables battery drain and charging; omitted fields take the built-in defaults.
+    battery: Option<BatteryEntry>,
+    #[serde(default)]
+    chargers: Vec<Placement>,
+}
+
+/// A kind of robot that `[[robots]]` entries refer to by `name`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RobotTypeEntry {
+    name: String,
+    max_speed: f32,
+    /// Units/s²; omitted for robots that reach top speed instantly.
+    acceleration: Option<f32>,
+    radius: f32,
+    #[serde(default = "default_load")]
+    payload: f32,
+    #[serde(default = "default_shape")]
+    shape: RobotShape,
+    /// `[r, g, b]` in 0..1; defaults to a colour picked by position in the list.
+    color: Option<[f32; 3]>,
+}
+
+/// Colours given to robot types that do not set one, in order.
+const TYPE_COLORS: [[f32; 3]; 4] = [
+    [0.95, 0.6, 0.1],
+    [0.6, 0.3, 0.85],
+    [0.1, 0.65, 0.7],
+    [0.85, 0.8, 0.2],
+];
+
+/// Battery model overrides, as fractions of a full battery.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatteryEntry {
+    drain_per_unit: Option<f32>,
+    idle_drain: Option<f32>,
+    charge_rate: Option<f32>,
+    threshold: Option<f32>,
+}
+
+impl BatteryEntry {
+    fn to_config(&self) -> BatteryConfig {
+        let defaults = BatteryConfig::default();
+        BatteryConfig {
+            drain_per_unit: self.drain_per_unit.unwrap_or(defaults.drain_per_unit),
+            idle_drain: self.idle_drain.unwrap_or(defaults.idle_drain),
+            charge_rate: self.charge_rate.unwrap_or(defaults.charge_rate),
+            threshold: self.threshold.unwrap_or(defaults.threshold),
+        }
+    }
+}
+
+/// A task arrival process, selected by `kind`.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum ArrivalEntry {
+    /// Random tasks at an average `rate` per second.
+    Poisson {
+        rate: f64,
+        #[serde(default)]
+        start: f64,
+        end: Option<f64>,
+        area: Option<AreaEntry>,
+        #[serde(default)]
+        dropoffs: Vec<[f32; 2]>,
+        #[serde(default)]
+        pickup_service: f64,
+        #[serde(default)]
+        dropoff_service: f64,
+        #[serde(default = "default_load")]
+        load: f32,
+        #[serde(default)]
+        priority: u32,
+        /// Seconds after release each task is due.
+        due_in: Option<f64>,
+    },
+    /// `count` random tasks at each of `times`.
+    Burst {
+        times: Vec<f64>,
+        count: usize,
+        area: Option<AreaEntry>,
+        #[serde(default)]
+        dropoffs: Vec<[f32; 2]>,
+        #[serde(default)]
+        pickup_service: f64,
+        #[serde(default)]
+        dropoff_service: f64,
+        #[serde(default = "default_load")]
+        load: f32,
+        #[serde(default)]
+        priority: u32,
+        /// Seconds after release each task is due.
+        due_in: Option<f64>,
+    },
+    /// A recorded order stream replayed at its release times.
+    Orders { orders: Vec<OrderEntry> },
+}
+
+/// Floor rectangle random pickups are drawn from.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AreaEntry {
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+/// One replayed order: a task released at `time`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrderEntry {
+    time: f64,
+    position: [f32; 2],
+    dropoff: Option<[f32; 2]>,
+    #[serde(default)]
+    pickup_service: f64,
+    #[serde(default)]
+    dropoff_service: f64,
+    #[serde(default = "default_load")]
+    load: f32,
+    #[serde(default)]
+    priority: u32,
+    /// Sim time the order should be completed by.
+    due: Option<f64>,
+}
+
+/// A blocked floor rectangle from `min` to `max`, each given as `[x, z]`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObstacleEntry {
+    kind: CellKind,
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+/// A task picked up at `position` and, if `dropoff` is given, delivered there.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskEntry {
+    position: [f32; 2],
+    dropoff: Option<[f32; 2]>,
+    /// Seconds spent loading at `position`.
+    #[serde(default)]
+    pickup_service: f64,
+    /// Seconds spent unloading at `dropoff`.
+    #[serde(default)]
+    dropoff_service: f64,
+    /// Payload a robot needs to carry the task.
+    #[serde(default = "default_load")]
+    load: f32,
+    /// Higher priorities are allocated ahead of nearer tasks.
+    #[serde(default)]
+    priority: u32,
+    /// Sim time the task should be completed by.
+    due: Option<f64>,
+    /// Indices of `[[tasks]]` entries that must be completed first.
+    #[serde(default)]
+    requires: Vec<usize>,
+}
+
+/// A robot of type `kind` starting at `position` with `charge` left in its battery.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RobotEntry {
+    position: [f32; 2],
+    #[serde(default = "default_charge")]
+    charge: f32,
+    /// Name of a robot type; `standard` when omitted.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// A floor position given as `[x, z]`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Placement {
+    position: [f32; 2],
+}
+
+fn default_charge() -> f32 {
+    1.0
+}
+
+fn default_load() -> f32 {
+    DEFAULT_LOAD
+}
+
+fn default_shape() -> RobotShape {
+    RobotShape::Box
+}
+
+fn default_arena_size() -> f32 {
+    DEFAULT_ARENA_SIZE
+}
+
+fn default_robot_speed() -> f32 {
+    DEFAULT_ROBOT_SPEED
+}
+
+fn default_collision_radius() -> f32 {
+    DEFAULT_COLLISION_RADIUS
+}
+
+fn default_cell_size() -> f32 {
+    DEFAULT_CELL_SIZE
+}
+
+/// Why a scenario file could not be loaded.
+#[derive(Debug)]
+pub enum ScenarioFileError {
+    /// The file could not be read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The extension is neither `.toml` nor `.json`.
+    UnsupportedFormat { path: PathBuf },
+    /// The contents are not valid TOML/JSON for the scenario schema.
+    Parse { path: PathBuf, message: String },
+    /// The contents parsed but describe an impossible scenario.
+    Invalid { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ScenarioFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => {
+                write!(f, "{}: cannot read file: {source}", path.display())
+            }
+            Self::UnsupportedFormat { path } => write!(
+                f,
+                "{}: unsupported format (expected a .toml or .json file)",
+                path.display()
+            ),
+            Self::Parse { path, message } => {
+                write!(f, "{}: parse error: {message}", path.display())
+            }
+            Self::Invalid { path, message } => {
+                write!(f, "{}: invalid scenario: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioFileError {}
+
+/// Loads and validates a single `.toml` or `.json` scenario file.
+pub fn load_scenario_file(path: &Path) -> Result<ScenarioDefinition, ScenarioFileError> {
+    let text = std::fs::read_to_string(path).map_err(|source| ScenarioFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let parse_error = |message: String| ScenarioFileError::Parse {
+        path: path.to_path_buf(),
+        message,
+    };
+    let file: ScenarioFile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&text).map_err(|err| parse_error(err.to_string()))?,
+        Some("json") => serde_json::from_str(&text).map_err(|err| parse_error(err.to_string()))?,
+        _ => {
+            return Err(ScenarioFileError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            });
+        }
+    };
+
+    validate(&file).map_err(|message| ScenarioFileError::Invalid {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    let name = file.name.clone().unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Custom".to_string())
+    });
+
+    let robot_types = resolve_robot_types(&file);
+    Ok(ScenarioDefinition {
+        name,
+        config: ScenarioConfig {
+            robots: file
+                .robots
+                .iter()
+                .map(|r| RobotSpec {
+                    position: Vec3::new(r.position[0], 0.5, r.position[1]),
+                    charge: r.charge,
+                    kind: type_index(&robot_types, r).unwrap_or(0),
+                })
+                .collect(),
+            tasks: file
+                .tasks
+                .iter()
+                .map(|t| TaskSpec {
+                    position: Vec3::new(t.position[0], 0.25, t.position[1]),
+                    dropoff: t.dropoff.map(|[x, z]| Vec3::new(x, 0.25, z)),
+                    pickup_service: t.pickup_service,
+                    dropoff_service: t.dropoff_service,
+                    load: t.load,
+                    priority: t.priority,
+                    due: t.due,
+                    requires: t.requires.clone(),
+                })
+                .collect(),
+            robot_types,
+            arena_size: file.arena_size,
+            cell_size: file.cell_size,
+            obstacles: file
+                .obstacles
+                .iter()
+                .map(|o| Obstacle::new(o.kind, Vec2::from(o.min), Vec2::from(o.max)))
+                .collect(),
+            allocator: file.allocator,
+            planner: file.planner,
+            arrivals: file.arrivals.iter().map(ArrivalEntry::to_process).collect(),
+            battery: file.battery.as_ref().map(BatteryEntry::to_config),
+            chargers: file
+                .chargers
+                .iter()
+                .map(|c| Vec3::new(c.position[0], 0.05, c.position[1]))
+                .collect(),
+            // Replaced by the run's seed when the scenario is built.
+            seed: 0,
+        },
+    })
+}
+
+/// The `standard` type built from the top-level speed and radius, followed by the file's
+/// own types; a file type named `standard` takes its place instead.
+fn resolve_robot_types(file: &ScenarioFile) -> Vec<RobotType> {
+    let mut types = vec![RobotType::standard(file.robot_speed, file.collision_radius)];
+    for (index, entry) in file.robot_types.iter().enumerate() {
+        let [r, g, b] = entry
+            .color
+            .unwrap_or(TYPE_COLORS[index % TYPE_COLORS.len()]);
+        let kind = RobotType {
+            name: entry.name.clone(),
+            max_speed: entry.max_speed,
+            acceleration: entry.acceleration,
+            radius: entry.radius,
+            payload: entry.payload,
+            shape: entry.shape,
+            color: Color::srgb(r, g, b),
+        };
+        if kind.name == types[0].name {
+            types[0] = kind;
+        } else {
+            types.push(kind);
+        }
+    }
+    types
+}
+
+/// Index of the robot's type in `types`, or `None` if the name is unknown.
+fn type_index(types: &[RobotType], robot: &RobotEntry) -> Option<usize> {
+    let name = robot.kind.as_deref().unwrap_or("standard");
+    types.iter().position(|kind| kind.name == name)
+}
+
+impl ArrivalEntry {
+    fn to_process(&self) -> ArrivalProcess {
+        let template = |area: &Option<AreaEntry>,
+                        dropoffs: &[[f32; 2]],
+                        pickup,
+                        dropoff,
+                        load,
+                        priority,
+                        due_in| TaskTemplate {
+            area: area
+                .as_ref()
+                .map(|a| (Vec2::from(a.min), Vec2::from(a.max))),
+            dropoffs: dropoffs
+                .iter()
+                .map(|&[x, z]| Vec3::new(x, 0.25, z))
+                .collect(),
+            pickup_service: pickup,
+            dropoff_service: dropoff,
+            load,
+            priority,
+            due_in,
+        };
+        match self {
+            Self::Poisson {
+                rate,
+                start,
+                end,
+                area,
+                dropoffs,
+                pickup_service,
+                dropoff_service,
+                load,
+                priority,
+                due_in,
+            } => ArrivalProcess::Poisson {
+                rate: *rate,
+                start: *start,
+                end: *end,
+                template: template(
+                    area,
+                    dropoffs,
+                    *pickup_service,
+                    *dropoff_service,
+                    *load,
+                    *priority,
+                    *due_in,
+                ),
+            },
+            Self::Burst {
+                times,
+                count,
+                area,
+                dropoffs,
+                pickup_service,
+                dropoff_service,
+                load,
+                priority,
+                due_in,
+            } => ArrivalProcess::Burst {
+                times: times.clone(),
+                count: *count,
+                template: template(
+                    area,
+                    dropoffs,
+                    *pickup_service,
+                    *dropoff_service,
+                    *load,
+                    *priority,
+                    *due_in,
+                ),
+            },
+            Self::Orders { orders } => ArrivalProcess::Orders(
+                orders
+                    .iter()
+                    .map(|o| {
+                        let spec = TaskSpec {
+                            position: Vec3::new(o.position[0], 0.25, o.position[1]),
+                            dropoff: o.dropoff.map(|[x, z]| Vec3::new(x, 0.25, z)),
+                            pickup_service: o.pickup_service,
+                            dropoff_service: o.dropoff_service,
+                            load: o.load,
+                            priority: o.priority,
+                            due: o.due,
+                            requires: Vec::new(),
+                        };
+                        (o.time, spec)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Loads every scenario file in `dir`, sorted by file name.
+///
+/// A missing directory yields no scenarios; each broken file is returned as an error
+/// alongside the files that did load, so one typo does not hide the rest.
+pub fn load_scenario_dir(dir: &Path) -> (Vec<ScenarioDefinition>, Vec<ScenarioFileError>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("toml" | "json")
+            )
+        })
+        .collect();
+    paths.sort();
+
+    let mut loaded = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        match load_scenario_file(&path) {
+            Ok(definition) => loaded.push(definition),
+            Err(err) => errors.push(err),
+        }
+    }
+    (loaded, errors)
+}
+
+/// Checks physical parameters and that every placement fits inside the arena.
+fn validate(file: &ScenarioFile) -> Result<(), String> {
+    if let Some(name) = &file.name
+        && name.trim().is_empty()
+    {
+        return Err("`name` must not be empty".to_string());
+    }
+    for (field, value) in [
+        ("arena_size", file.arena_size),
+        ("robot_speed", file.robot_speed),
+        ("collision_radius", file.collision_radius),
+        ("cell_size", file.cell_size),
+    ] {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(format!("`{field}` must be a positive number, got {value}"));
+        }
+    }
+    if file.cell_size > file.arena_size {
+        return Err(format!(
+            "`cell_size` ({}) must not exceed `arena_size` ({})",
+            file.cell_size, file.arena_size
+        ));
+    }
+    if file.robots.is_empty() {
+        return Err("at least one robot is required".to_string());
+    }
+
+    let half = file.arena_size / 2.0;
+    for (index, obstacle) in file.obstacles.iter().enumerate() {
+        let (min, max) = (Vec2::from(obstacle.min), Vec2::from(obstacle.max));
+        if obstacle.kind == CellKind::Free {
+            return Err(format!(
+                "obstacle {index} has kind `free`, which blocks nothing"
+            ));
+        }
+        if !min.is_finite() || !max.is_finite() || min.cmpge(max).any() {
+            return Err(format!(
+                "obstacle {index} must have `min` strictly below `max` on both axes, got {:?}..{:?}",
+                obstacle.min, obstacle.max
+            ));
+        }
+        if min.min_element() < -half || max.max_element() > half {
+            return Err(format!(
+                "obstacle {index} {:?}..{:?} extends outside the arena (-{half}..{half})",
+                obstacle.min, obstacle.max
+            ));
+        }
+    }
+    for (index, task) in file.tasks.iter().enumerate() {
+        for (field, value) in [
+            ("pickup_service", task.pickup_service),
+            ("dropoff_service", task.dropoff_service),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!(
+                    "task {index} `{field}` must be zero or more, got {value}"
+                ));
+            }
+        }
+        if task.dropoff.is_none() && task.dropoff_service > 0.0 {
+            return Err(format!(
+                "task {index} has a `dropoff_service` but no `dropoff`"
+            ));
+        }
+    }
+
+    let types = resolve_robot_types(file);
+    validate_robot_types(file)?;
+    for (index, robot) in file.robots.iter().enumerate() {
+        if !(0.0..=1.0).contains(&robot.charge) {
+            return Err(format!(
+                "robot {index} `charge` must be between 0 and 1, got {}",
+                robot.charge
+            ));
+        }
+        if type_index(&types, robot).is_none() {
+            return Err(format!(
+                "robot {index} has unknown type `{}`",
+                robot.kind.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    let radius_of = |robot: &RobotEntry| type_index(&types, robot).map_or(0.0, |i| types[i].radius);
+    let fleet_payload = file
+        .robots
+        .iter()
+        .filter_map(|robot| type_index(&types, robot))
+        .map(|i| types[i].payload)
+        .fold(0.0, f32::max);
+    for (index, task) in file.tasks.iter().enumerate() {
+        check_load(&format!("task {index}"), task.load, fleet_payload)?;
+        if let Some(due) = task.due
+            && (!due.is_finite() || due < 0.0)
+        {
+            return Err(format!(
+                "task {index} `due` must be zero or more, got {due}"
+            ));
+        }
+        if let Some(missing) = task.requires.iter().find(|&&id| id >= file.tasks.len()) {
+            return Err(format!(
+                "task {index} requires task {missing}, which does not exist"
+            ));
+        }
+    }
+    if let Some(cycle) = find_dependency_cycle(&file.tasks) {
+        let path: Vec<String> = cycle.iter().map(usize::to_string).collect();
+        return Err(format!(
+            "task dependencies form a cycle (each task requires the next): {}",
+            path.join(" -> ")
+        ));
+    }
+    validate_battery(file)?;
+
+    let placements = file
+        .robots
+        .iter()
+        .enumerate()
+        .map(|(i, r)| ("robot", i, r.position))
+        .chain(
+            file.chargers
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ("charger", i, c.position)),
+        )
+        .chain(
+            file.tasks
+                .iter()
+                .enumerate()
+                .map(|(i, t)| ("task", i, t.position)),
+        )
+        .chain(
+            file.tasks
+                .iter()
+                .enumerate()
+                .filter_map(|(i, t)| t.dropoff.map(|dropoff| ("task dropoff", i, dropoff))),
+        );
+    for (kind, index, position) in placements {
+        let [x, z] = position;
+        if !x.is_finite() || !z.is_finite() || x.abs() > half || z.abs() > half {
+            return Err(format!(
+                "{kind} {index} position [{x}, {z}] is outside the arena (-{half}..{half})"
+            ));
+        }
+    }
+
+    // Robots need their whole footprint clear, and chargers room for the widest robot;
+    // tasks only need their marker on open floor.
+    let widest = file.robots.iter().map(radius_of).fold(0.0, f32::max);
+    for (index, obstacle) in file.obstacles.iter().enumerate() {
+        let rect = Obstacle::new(
+            obstacle.kind,
+            Vec2::from(obstacle.min),
+            Vec2::from(obstacle.max),
+        );
+        let blocked = file
+            .robots
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| rect.contains(Vec2::from(r.position), radius_of(r)))
+            .map(|(i, _)| ("robot", i))
+            .chain(
+                file.chargers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| rect.contains(Vec2::from(c.position), widest))
+                    .map(|(i, _)| ("charger", i)),
+            )
+            .chain(
+                file.tasks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, t)| rect.contains(Vec2::from(t.position), 0.0))
+                    .map(|(i, _)| ("task", i)),
+            )
+            .chain(
+                file.tasks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, t)| t.dropoff.is_some_and(|d| rect.contains(Vec2::from(d), 0.0)))
+                    .map(|(i, _)| ("task dropoff", i)),
+            )
+            .next();
+        if let Some((kind, i)) = blocked {
+            return Err(format!("{kind} {i} is placed on obstacle {index}"));
+        }
+    }
+
+    // Robots overlapping at spawn would be in conflict before the run starts.
+    for (i, a) in file.robots.iter().enumerate() {
+        for (j, b) in file.robots.iter().enumerate().skip(i + 1) {
+            let gap = Vec2::from(a.position).distance(Vec2::from(b.position));
+            let min_gap = radius_of(a) + radius_of(b);
+            if gap < min_gap {
+                return Err(format!(
+                    "robots {i} and {j} start {gap:.2} apart, closer than their combined radii ({min_gap:.2})"
+                ));
+            }
+        }
+    }
+
+    validate_arrivals(file, fleet_payload)
+}
+
+/// Returns the tasks along a dependency cycle, first task repeated at the end, if there is one.
+///
+/// Depth-first search over `requires` edges; an edge back to a task still on the current
+/// path closes a cycle. Expects every required id to be in range.
+fn find_dependency_cycle(tasks: &[TaskEntry]) -> Option<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        OnPath,
+        Done,
+    }
+    let mut marks = vec![Mark::Unvisited; tasks.len()];
+
+    for root in 0..tasks.len() {
+        if marks[root] != Mark::Unvisited {
+            continue;
+        }
+        // Each frame is a task on the current path and how many of its edges were followed.
+        let mut path = vec![(root, 0)];
+        marks[root] = Mark::OnPath;
+        while let Some(&mut (task, ref mut next)) = path.last_mut() {
+            let Some(&required) = tasks[task].requires.get(*next) else {
+                marks[task] = Mark::Done;
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            match marks[required] {
+                Mark::Unvisited => {
+                    marks[required] = Mark::OnPath;
+                    path.push((required, 0));
+                }
+                Mark::OnPath => {
+                    let start = path.iter().position(|&(id, _)| id == required).unwrap_or(0);
+                    let mut cycle: Vec<usize> = path[start..].iter().map(|&(id, _)| id).collect();
+                    cycle.push(required);
+                    return Some(cycle);
+                }
+                Mark::Done => {}
+            }
+        }
+    }
+    None
+}
+
+/// Checks robot type parameters and that names are unique.
+fn validate_robot_types(file: &ScenarioFile) -> Result<(), String> {
+    for (index, entry) in file.robot_types.iter().enumerate() {
+        let what = format!("robot type {index}");
+        if entry.name.trim().is_empty() {
+            return Err(format!("{what} `name` must not be empty"));
+        }
+        if file.robot_types[..index]
+            .iter()
+            .any(|other| other.name == entry.name)
+        {
+            return Err(format!("{what} reuses the name `{}`", entry.name));
+        }
+        for (field, value) in [
+            ("max_speed", Some(entry.max_speed)),
+            ("acceleration", entry.acceleration),
+            ("radius", Some(entry.radius)),
+            ("payload", Some(entry.payload)),
+        ] {
+            if let Some(value) = value
+                && (!value.is_finite() || value <= 0.0)
+            {
+                return Err(format!(
+                    "{what} `{field}` must be a positive number, got {value}"
+                ));
+            }
+        }
+        if let Some(color) = entry.color
+            && color.iter().any(|c| !(0.0..=1.0).contains(c))
+        {
+            return Err(format!(
+                "{what} `color` components must be between 0 and 1, got {color:?}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a task load is positive and light enough for some robot in the fleet.
+fn check_load(what: &str, load: f32, fleet_payload: f32) -> Result<(), String> {
+    if !load.is_finite() || load <= 0.0 {
+        return Err(format!(
+            "{what} `load` must be a positive number, got {load}"
+        ));
+    }
+    if load > fleet_payload {
+        return Err(format!(
+            "{what} `load` ({load}) exceeds the largest payload in the fleet ({fleet_payload})"
+        ));
+    }
+    Ok(())
+}
+
+/// Checks battery parameters and that a battery model comes with somewhere to charge.
+fn validate_battery(file: &ScenarioFile) -> Result<(), String> {
+    let Some(entry) = &file.battery else {
+        if !file.chargers.is_empty() {
+            return Err("`chargers` need a `[battery]` section to have any effect".to_string());
+        }
+        return Ok(());
+    };
+    let battery = entry.to_config();
+    for (field, value) in [
+        ("drain_per_unit", battery.drain_per_unit),
+        ("idle_drain", battery.idle_drain),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!(
+                "battery `{field}` must be zero or more, got {value}"
+            ));
+        }
+    }
+    if !battery.charge_rate.is_finite() || battery.charge_rate <= 0.0 {
+        return Err(format!(
+            "battery `charge_rate` must be a positive number, got {}",
+            battery.charge_rate
+        ));
+    }
+    if !(0.0..1.0).contains(&battery.threshold) {
+        return Err(format!(
+            "battery `threshold` must be at least 0 and below 1, got {}",
+            battery.threshold
+        ));
+    }
+    if file.chargers.is_empty() {
+        return Err("a `[battery]` section needs at least one entry in `chargers`".to_string());
+    }
+    Ok(())
+}
+
+/// Checks arrival rates, times, sampling areas, dropoffs, loads and replayed orders.
+fn validate_arrivals(file: &ScenarioFile, fleet_payload: f32) -> Result<(), String> {
+    let half = file.arena_size / 2.0;
+    let on_floor = |what: &str, [x, z]: [f32; 2]| -> Result<(), String> {
+        if !x.is_finite() || !z.is_finite() || x.abs() > half || z.abs() > half {
+            return Err(format!(
+                "{what} [{x}, {z}] is outside the arena (-{half}..{half})"
+            ));
+        }
+        let point = Vec2::new(x, z);
+        if let Some(index) = file.obstacles.iter().position(|o| {
+            Obstacle::new(o.kind, Vec2::from(o.min), Vec2::from(o.max)).contains(point, 0.0)
+        }) {
+            return Err(format!("{what} [{x}, {z}] is placed on obstacle {index}"));
+        }
+        Ok(())
+    };
+    let time = |what: &str, value: f64| -> Result<(), String> {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("{what} must be zero or more, got {value}"));
+        }
+        Ok(())
+    };
+
+    for (index, arrival) in file.arrivals.iter().enumerate() {
+        let what = format!("arrival {index}");
+        let (area, dropoffs, pickup_service, dropoff_service, load, due_in) = match arrival {
+            ArrivalEntry::Poisson {
+                rate,
+                start,
+                end,
+                area,
+                dropoffs,
+                pickup_service,
+                dropoff_service,
+                load,
+                priority: _,
+                due_in,
+            } => {
+                if !rate.is_finite() || *rate <= 0.0 {
+                    return Err(format!(
+                        "{what} `rate` must be a positive number, got {rate}"
+                    ));
+                }
+                time(&format!("{what} `start`"), *start)?;
+                if let Some(end) = end
+                    && !(end.is_finite() && end > start)
+                {
+                    return Err(format!(
+                        "{what} `end` ({end}) must come after `start` ({start})"
+                    ));
+                }
+                (
+                    area,
+                    dropoffs,
+                    *pickup_service,
+                    *dropoff_service,
+                    *load,
+                    *due_in,
+                )
+            }
+            ArrivalEntry::Burst {
+                times,
+                count,
+                area,
+                dropoffs,
+                pickup_service,
+                dropoff_service,
+                load,
+                priority: _,
+                due_in,
+            } => {
+                if *count == 0 || times.is_empty() {
+                    return Err(format!(
+                        "{what} needs a positive `count` and at least one time"
+                    ));
+                }
+                for &t in times {
+                    time(&format!("{what} burst time"), t)?;
+                }
+                (
+                    area,
+                    dropoffs,
+                    *pickup_service,
+                    *dropoff_service,
+                    *load,
+                    *due_in,
+                )
+            }
+            ArrivalEntry::Orders { orders } => {
+                for (order_index, order) in orders.iter().enumerate() {
+                    let what = format!("{what} order {order_index}");
+                    time(&format!("{what} `time`"), order.time)?;
+                    time(&format!("{what} `pickup_service`"), order.pickup_service)?;
+                    time(&format!("{what} `dropoff_service`"), order.dropoff_service)?;
+                    if order.dropoff.is_none() && order.dropoff_service > 0.0 {
+                        return Err(format!("{what} has a `dropoff_service` but no `dropoff`"));
+                    }
+                    check_load(&what, order.load, fleet_payload)?;
+                    if let Some(due) = order.due
+                        && !(due.is_finite() && due >= order.time)
+                    {
+                        return Err(format!(
+                            "{what} `due` ({due}) must not come before its `time` ({})",
+                            order.time
+                        ));
+                    }
+                    on_floor(&format!("{what} position"), order.position)?;
+                    if let Some(dropoff) = order.dropoff {
+                        on_floor(&format!("{what} dropoff"), dropoff)?;
+                    }
+                }
+                continue;
+            }
+        };
+
+        time(&format!("{what} `pickup_service`"), pickup_service)?;
+        time(&format!("{what} `dropoff_service`"), dropoff_service)?;
+        check_load(&what, load, fleet_payload)?;
+        if let Some(due_in) = due_in
+            && (!due_in.is_finite() || due_in <= 0.0)
+        {
+            return Err(format!(
+                "{what} `due_in` must be a positive number, got {due_in}"
+            ));
+        }
+        if dropoffs.is_empty() && dropoff_service > 0.0 {
+            return Err(format!("{what} has a `dropoff_service` but no `dropoffs`"));
+        }
+        for &dropoff in dropoffs {
+            on_floor(&format!("{what} dropoff"), dropoff)?;
+        }
+        if let Some(area) = area {
+            let (min, max) = (Vec2::from(area.min), Vec2::from(area.max));
+            if !min.is_finite() || !max.is_finite() || min.cmpge(max).any() {
+                return Err(format!(
+                    "{what} `area` must have `min` strictly below `max` on both axes"
+                ));
+            }
+            if min.min_element() < -half || max.max_element() > half {
+                return Err(format!(
+                    "{what} `area` extends outside the arena (-{half}..{half})"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads `text` as a scenario file named `name`, which picks the format.
+    fn load(name: &str, text: &str) -> Result<ScenarioDefinition, ScenarioFileError> {
+        let dir = std::env::temp_dir().join(format!("fleetsim-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        let loaded = load_scenario_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn error(name: &str, text: &str) -> String {
+        match load(name, text) {
+            Ok(_) => panic!("{name} loaded"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let message = error(
+            "unknown_field.toml",
+            "robot_sped = 3.0\n[[robots]]\nposition = [0.0, 0.0]\n",
+        );
+        assert!(message.contains("parse error"), "{message}");
+        assert!(message.contains("unknown field `robot_sped`"), "{message}");
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let message = error(
+            "charge.json",
+            r#"{ "robots": [{ "position": [0.0, 0.0], "charge": 1.5 }] }"#,
+        );
+        assert!(message.contains("invalid scenario"), "{message}");
+        assert!(
+            message.contains("robot 0 `charge` must be between 0 and 1, got 1.5"),
+            "{message}"
+        );
+
+        let message = error(
+            "outside.toml",
+            "arena_size = 10.0\n[[robots]]\nposition = [6.0, 0.0]\n",
+        );
+        assert!(
+            message.contains("robot 0 position [6, 0] is outside the arena"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn overlapping_robots_are_rejected() {
+        let message = error(
+            "overlap.toml",
+            "[[robots]]\nposition = [0.0, 0.0]\n[[robots]]\nposition = [0.5, 0.0]\n",
+        );
+        assert!(
+            message.contains("robots 0 and 1 start 0.50 apart, closer than their combined radii"),
+            "{message}"
+        );
+    }
+
+    /// Tasks at the origin, each requiring the listed tasks.
+    fn tasks(requires: &[&[usize]]) -> Vec<TaskEntry> {
+        requires
+            .iter()
+            .map(|requires| TaskEntry {
+                position: [0.0, 0.0],
+                dropoff: None,
+                pickup_service: 0.0,
+                dropoff_service: 0.0,
+                load: DEFAULT_LOAD,
+                priority: 0,
+                due: None,
+                requires: requires.to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_task_requiring_itself_is_a_cycle() {
+        assert_eq!(
+            find_dependency_cycle(&tasks(&[&[], &[1]])),
+            Some(vec![1, 1])
+        );
+    }
+
+    #[test]
+    fn longer_cycles_are_reported_in_order() {
+        let tasks = tasks(&[&[], &[2], &[3], &[0, 4], &[1]]);
+        assert_eq!(find_dependency_cycle(&tasks), Some(vec![1, 2, 3, 4, 1]));
+    }
+
+    #[test]
+    fn a_dependency_dag_has_no_cycle() {
+        // A diamond: both middle tasks wait on the first, the last waits on both.
+        let tasks = tasks(&[&[], &[0], &[0], &[1, 2], &[3, 0]]);
+        assert_eq!(find_dependency_cycle(&tasks), None);
+    }
+
+    #[test]
+    fn unknown_prerequisites_are_rejected() {
+        let message = error(
+            "requires.toml",
+            "[[robots]]\nposition = [0.0, 0.0]\n[[tasks]]\nposition = [2.0, 0.0]\nrequires = [3]\n",
+        );
+        assert!(
+            message.contains("task 0 requires task 3, which does not exist"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn shipped_scenarios_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCENARIO_DIR);
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        let (loaded, errors) = load_scenario_dir(&dir);
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert!(errors.is_empty(), "{errors:#?}");
+        assert_eq!(loaded.len(), files);
+    }
+}
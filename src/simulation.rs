@@ -1,59 +1,131 @@
 use bevy::prelude::*;
 use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
 
+use crate::allocation::{ActiveAllocator, Assignment, IdleRobot, OpenTask};
+use crate::arrivals::TaskArrivals;
+use crate::conflicts::{ConflictForecast, Trajectory, first_contact};
+use crate::map::WarehouseMap;
+use crate::mapf::{Agent, plan_joint};
 use crate::model::{
-    ActiveScenario, CollisionState, Event, EventType, Robot, RobotAssignment, RobotPath,
-    RobotVisualMaterials, ScenarioConfig, Simulation, Task,
+    ActiveScenario, Battery, ChargingStation, CollisionState, EntityIndex, Event, EventType,
+    Ground, Robot, RobotAssignment, RobotMaterial, RobotMotion, RobotPath, RobotShape, RobotState,
+    RobotType, RobotVisualMaterials, ScenarioConfig, ScenarioLibrary, Simulation, SimulationClock,
+    SimulationSeed, Task, TaskSpec, TrafficState,
+};
+use crate::pathfinding::{path_length, plan_path, plan_path_around};
+use crate::reservation::{
+    ActivePlanner, MIN_LEG_TIME, PlannerKind, ReservationTable, blocks_route, plan_reserved,
+};
+use crate::spatial::{Neighbour, SpatialIndex};
+use crate::traffic::{
+    BACKOFF_WAIT, DeadlockCount, DeadlockPolicy, LOOKAHEAD, STEP_ASIDE_MARGIN, TrafficControl,
+    WaitForGraph, YIELD_HOLD, blocker_ahead, route_ahead, side_step, splice_detour,
 };
 
-/// Spawns robots and tasks from a resolved `ScenarioConfig`.
-pub fn spawn_scenario(
-    commands: &mut Commands,
-    meshes: &mut Assets<Mesh>,
-    materials: &mut Assets<StandardMaterial>,
-    visuals: &RobotVisualMaterials,
-    config: &ScenarioConfig,
-) {
-    for (id, &pos) in config.robot_positions.iter().enumerate() {
-        commands.spawn((
-            Mesh3d(meshes.add(Cuboid::default())),
-            MeshMaterial3d(visuals.normal.clone()),
+/// Spawns robots, tasks and charging stations from a resolved `ScenarioConfig`.
+///
+/// Only simulation state is spawned here; meshes and materials are attached by
+/// `attach_robot_visuals` / `attach_task_visuals` so headless runs can share it.
+pub fn spawn_scenario(commands: &mut Commands, config: &ScenarioConfig) {
+    commands.insert_resource(config.clone());
+    commands.insert_resource(WarehouseMap::from_config(config));
+    if let Some(kind) = config.allocator {
+        commands
+            .queue(move |world: &mut World| world.resource_mut::<ActiveAllocator>().select(kind));
+    }
+    if let Some(kind) = config.planner {
+        commands.queue(move |world: &mut World| world.resource_mut::<ActivePlanner>().kind = kind);
+    }
+
+    // Every robot starts out holding its spawn position.
+    let mut reservations = ReservationTable::default();
+    let mut index = EntityIndex::default();
+
+    for (id, spec) in config.robots.iter().enumerate() {
+        let pos = spec.position;
+        let kind = &config.robot_types[spec.kind];
+        reservations.park(id, kind.radius, pos);
+        let entity = commands.spawn((
             Transform::from_translation(pos),
-            Robot { id },
+            Robot { id, home: pos },
             RobotAssignment::default(),
-            RobotPath { points: vec![pos] },
+            RobotPath {
+                points: vec![pos],
+                planned: Vec::new(),
+            },
+            RobotMotion::default(),
+            RobotState::default(),
+            Battery::new(spec.charge),
             CollisionState::default(),
+            TrafficState::default(),
+            kind.clone(),
         ));
+        index.insert_robot(id, entity.id());
     }
+    commands.insert_resource(reservations);
+    commands.insert_resource(DeadlockCount::default());
+    commands.insert_resource(ConflictForecast::default());
+    commands.insert_resource(SpatialIndex::default());
 
-    for (id, &pos) in config.task_positions.iter().enumerate() {
-        commands.spawn((
-            Mesh3d(meshes.add(Cuboid::default())),
-            MeshMaterial3d(materials.add(Color::srgb(0.9, 0.3, 0.2))),
-            Transform::from_translation(pos).with_scale(Vec3::splat(0.3)),
+    for (id, &pos) in config.chargers.iter().enumerate() {
+        commands.spawn((Transform::from_translation(pos), ChargingStation { id }));
+    }
+
+    for (id, spec) in config.tasks.iter().enumerate() {
+        index.insert_task(id, spawn_task(commands, id, spec, 0.0));
+    }
+    // Replaces the index of any scenario despawned before this one.
+    commands.insert_resource(index);
+
+    // Scheduled once applied, so a reset's cleared event queue is refilled afterwards.
+    let mut arrivals = TaskArrivals::from_config(config);
+    commands.queue(move |world: &mut World| {
+        arrivals.start(&mut world.resource_mut::<Simulation>());
+        world.insert_resource(arrivals);
+    });
+}
+
+/// Spawns one task entity released at sim time `released_at`.
+pub fn spawn_task(commands: &mut Commands, id: usize, spec: &TaskSpec, released_at: f64) -> Entity {
+    commands
+        .spawn((
+            Transform::from_translation(spec.position).with_scale(Vec3::splat(0.3)),
             Task {
                 id,
                 assigned_to: None,
                 completed: false,
+                unreachable: false,
+                load: spec.load,
+                max_radius: f32::INFINITY,
+                dropoff: spec.dropoff,
+                pickup_service: spec.pickup_service,
+                dropoff_service: spec.dropoff_service,
+                picked_up: false,
+                released_at,
+                completed_at: None,
+                priority: spec.priority,
+                due: spec.due,
+                requires: spec.requires.clone(),
             },
-        ));
-    }
+        ))
+        .id()
 }
 
-/// Spawns world content: light, ground, robots, and tasks.
+/// Spawns world content: light, ground, map obstacles, robots, and tasks.
 pub fn setup_world(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     active: Res<ActiveScenario>,
+    seed: Res<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
 ) {
-    let robot_normal_material = materials.add(Color::srgb(0.2, 0.7, 0.3));
-    let robot_collision_material = materials.add(Color::srgb(1.0, 0.2, 0.1));
     let visuals = RobotVisualMaterials {
-        normal: robot_normal_material,
-        collision: robot_collision_material,
+        collision: materials.add(Color::srgb(1.0, 0.2, 0.1)),
+        deadlock: materials.add(Color::srgb(0.7, 0.2, 0.9)),
     };
-    commands.insert_resource(visuals.clone());
+    commands.insert_resource(visuals);
 
     // Directional light for simple scene illumination.
     commands.spawn((
@@ -61,177 +133,1806 @@ pub fn setup_world(
         Transform::from_xyz(5.0, 10.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
     ));
 
-    // Large scaled plane used as ground.
+    let config = ScenarioConfig::build(active.0, seed.0, &library);
+
+    // Unit plane scaled to the arena size; `resize_ground` follows scenario changes.
     commands.spawn((
         Mesh3d(meshes.add(Plane3d::default())),
         MeshMaterial3d(materials.add(Color::srgb(0.8, 0.8, 0.8))),
-        Transform::from_scale(Vec3::splat(20.0)),
+        Transform::from_scale(Vec3::splat(config.arena_size)),
+        Ground,
     ));
 
-    let config = ScenarioConfig::build(active.0);
-    spawn_scenario(&mut commands, &mut meshes, &mut materials, &visuals, &config);
+    spawn_scenario(&mut commands, &config);
+}
+
+/// Rescales the ground plane when a scenario with a different arena size is loaded.
+pub fn resize_ground(config: Res<ScenarioConfig>, mut ground: Query<&mut Transform, With<Ground>>) {
+    if !config.is_changed() {
+        return;
+    }
+    for mut transform in &mut ground {
+        transform.scale = Vec3::splat(config.arena_size);
+    }
+}
+
+/// Gives newly spawned robots a mesh sized to their footprint and a material in their
+/// type's colour.
+pub fn attach_robot_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    robots: Query<(Entity, &RobotType), Added<Robot>>,
+) {
+    for (entity, kind) in &robots {
+        // The standard 0.4 radius keeps the original unit cube.
+        let width = kind.radius * 2.5;
+        let mesh = match kind.shape {
+            RobotShape::Box => meshes.add(Cuboid::new(width, 1.0, width)),
+            RobotShape::Cylinder => meshes.add(Cylinder::new(width / 2.0, 1.0)),
+        };
+        let material = materials.add(kind.color);
+        commands.entity(entity).insert((
+            Mesh3d(mesh),
+            MeshMaterial3d(material.clone()),
+            RobotMaterial(material),
+        ));
+    }
+}
+
+/// Gives newly spawned charging stations a flat pad mesh.
+pub fn attach_station_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    stations: Query<Entity, Added<ChargingStation>>,
+) {
+    for entity in &stations {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cylinder::new(0.6, 0.1))),
+            MeshMaterial3d(materials.add(Color::srgb(0.2, 0.45, 0.95))),
+        ));
+    }
+}
+
+/// Gives newly spawned tasks their marker mesh and material.
+pub fn attach_task_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tasks: Query<Entity, Added<Task>>,
+) {
+    for entity in &tasks {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cuboid::default())),
+            MeshMaterial3d(materials.add(Color::srgb(0.9, 0.3, 0.2))),
+        ));
+    }
 }
 
-/// Advances simulation time and executes due events.
+/// Robot components touched when executing events.
+type RobotEventData = (
+    &'static Robot,
+    &'static mut Transform,
+    &'static mut RobotAssignment,
+    &'static mut RobotPath,
+    &'static mut RobotMotion,
+    &'static mut RobotState,
+    &'static mut Battery,
+);
+
+/// Advances simulation time by one tick and executes due events.
+///
+/// Runs in `FixedUpdate` with the app, so results don't depend on the frame rate. An
+/// event-driven clock jumps straight to the next event instead, so the systems after this
+/// one run once per event time.
+#[allow(clippy::too_many_arguments)]
 pub fn run_simulation(
-    time: Res<Time>,
+    mut commands: Commands,
+    mut clock: ResMut<SimulationClock>,
     mut sim: ResMut<Simulation>,
-    mut robots: Query<(&Robot, &mut Transform, &mut RobotAssignment, &mut RobotPath)>,
+    mut arrivals: ResMut<TaskArrivals>,
+    mut forecast: ResMut<ConflictForecast>,
+    mut index: ResMut<EntityIndex>,
+    mut robots: Query<RobotEventData>,
     mut tasks: Query<&mut Task>,
 ) {
-    // Advance simulation clock by frame delta.
-    sim.now += time.delta_secs_f64();
+    if clock.paused {
+        // While paused, only an explicit step pops (exactly one) event.
+        if !clock.step_requested {
+            return;
+        }
+        clock.step_requested = false;
+
+        let Some(event) = sim.pop_next() else {
+            return;
+        };
+        sim.now = sim.now.max(event.timestamp);
+        execute_event(
+            event,
+            &mut sim,
+            &mut arrivals,
+            &mut forecast,
+            &mut index,
+            &mut commands,
+            &mut robots,
+            &mut tasks,
+        );
+        return;
+    }
+
+    let next = if clock.event_driven {
+        // Jump to the next event, or tick on while there is none.
+        let next = sim.next_event_time().unwrap_or(sim.now + clock.step);
+        next.clamp(sim.now + clock.min_jump, sim.now + clock.max_jump)
+    } else {
+        // Advance by one fixed step; the clock's speed sets how often ticks come.
+        sim.now + clock.step
+    };
+    sim.now = next.min(clock.until).max(sim.now);
 
     // Process all events whose timestamp is now due.
-    while let Some(event) = sim.events.peek() {
-        if event.timestamp > sim.now {
-            break;
+    while let Some(event) = sim.pop_due() {
+        execute_event(
+            event,
+            &mut sim,
+            &mut arrivals,
+            &mut forecast,
+            &mut index,
+            &mut commands,
+            &mut robots,
+            &mut tasks,
+        );
+    }
+}
+
+/// Applies a single popped event to the world.
+#[allow(clippy::too_many_arguments)]
+fn execute_event(
+    event: Event,
+    sim: &mut Simulation,
+    arrivals: &mut TaskArrivals,
+    forecast: &mut ConflictForecast,
+    index: &mut EntityIndex,
+    commands: &mut Commands,
+    robots: &mut Query<RobotEventData>,
+    tasks: &mut Query<&mut Task>,
+) {
+    match event.event_type {
+        EventType::DepartRobot { robot_id, route } => {
+            // Start following the route; `update_robot_motion` moves the robot from here on.
+            let Some(Ok((_, _, _, mut path, mut motion, mut state, _))) =
+                index.robot(robot_id).map(|entity| robots.get_mut(entity))
+            else {
+                return;
+            };
+            // A robot rerouted on its way home keeps the part it already drove.
+            let driven = motion
+                .waypoints
+                .iter()
+                .filter(|&&(t, _)| t < event.timestamp);
+            path.points.extend(driven.skip(1).map(|&(_, point)| point));
+            if let Some(&(_, start)) = route.first()
+                && !motion.waypoints.is_empty()
+            {
+                path.points.push(start);
+            }
+            motion.waypoints = route;
+            *state = RobotState::TravellingEmpty;
         }
+        EventType::MoveRobot {
+            robot_id,
+            target,
+            task_id,
+        } => {
+            // Snap the targeted robot onto its destination and update its path history.
+            if let Some(Ok(robot)) = index.robot(robot_id).map(|entity| robots.get_mut(entity)) {
+                let (_, mut transform, mut assignment, mut path, mut motion, mut state, _) = robot;
 
-        let event = sim.events.pop().unwrap();
-        match event.event_type {
-            EventType::MoveRobot {
-                robot_id,
-                target,
-                task_id,
-            } => {
-                // Move the targeted robot and update its path history.
-                for (robot, mut transform, mut assignment, mut path) in &mut robots {
-                    if robot.id != robot_id {
-                        continue;
-                    }
-
-                    let from = transform.translation;
-                    transform.translation = target;
-                    if path.points.last().copied() != Some(from) {
-                        path.points.push(from);
-                    }
-                    path.points.push(target);
-                    assignment.task_id = None;
+                // Reserved routes carry on home after the last stop; the robot drives on
+                // unless it is given new work first.
+                let onward: Vec<(f64, Vec3)> = motion
+                    .waypoints
+                    .iter()
+                    .copied()
+                    .filter(|&(t, _)| t > event.timestamp)
+                    .collect();
+                finish_route(&mut transform, &mut path, &mut motion, target);
+                if !onward.is_empty() {
+                    path.planned = std::iter::once(target)
+                        .chain(onward.iter().map(|&(_, point)| point))
+                        .collect();
+                    motion.waypoints = std::iter::once((event.timestamp, target))
+                        .chain(onward)
+                        .collect();
                 }
+                assignment.task_id = None;
+                assignment.repositioning = false;
+                *state = RobotState::Idle;
+            }
 
-                // Mark the task as completed once the robot "arrives".
-                if let Some(task_id) = task_id {
-                    for mut task in &mut tasks {
-                        if task.id == task_id {
-                            task.completed = true;
-                            task.assigned_to = None;
-                            break;
-                        }
-                    }
-                }
+            // Mark the task as completed once the robot "arrives"; `allocate_tasks` starts
+            // the robot's next queued task, if any.
+            if let Some(Ok(mut task)) = task_id
+                .and_then(|id| index.task(id))
+                .map(|entity| tasks.get_mut(entity))
+            {
+                task.completed = true;
+                task.completed_at = Some(event.timestamp);
+                task.assigned_to = None;
+            }
+        }
+        EventType::StartLoading { robot_id } => {
+            set_robot_state(robots, index, robot_id, RobotState::Loading);
+        }
+        EventType::FinishLoading { robot_id, task_id } => {
+            set_robot_state(robots, index, robot_id, RobotState::Carrying);
+            if let Some(Ok(mut task)) = index.task(task_id).map(|entity| tasks.get_mut(entity)) {
+                task.picked_up = true;
+            }
+        }
+        EventType::StartUnloading { robot_id } => {
+            set_robot_state(robots, index, robot_id, RobotState::Unloading);
+        }
+        EventType::ChargeStart { robot_id, target } => {
+            if let Some(Ok((_, mut transform, _, mut path, mut motion, mut state, _))) =
+                index.robot(robot_id).map(|entity| robots.get_mut(entity))
+            {
+                finish_route(&mut transform, &mut path, &mut motion, target);
+                *state = RobotState::Charging;
             }
         }
+        EventType::ChargeEnd { robot_id } => {
+            if let Some(Ok((_, _, _, _, _, mut state, mut battery))) =
+                index.robot(robot_id).map(|entity| robots.get_mut(entity))
+            {
+                // The session was sized to fill the battery; settle any per-frame drift.
+                battery.level = 1.0;
+                battery.station = None;
+                battery.charges += 1;
+                *state = RobotState::Idle;
+            }
+        }
+        EventType::TaskArrival {
+            process,
+            index: arrival,
+        } => {
+            // New tasks become visible to `allocate_tasks` once the spawn commands apply.
+            for (id, spec) in arrivals.arrive(sim, event.timestamp, process, arrival) {
+                index.insert_task(id, spawn_task(commands, id, &spec, event.timestamp));
+            }
+        }
+        EventType::AwardTask { robot_id, task_id } => {
+            if let Some(Ok((_, _, mut assignment, ..))) =
+                index.robot(robot_id).map(|entity| robots.get_mut(entity))
+            {
+                assignment.pending_awards = assignment.pending_awards.saturating_sub(1);
+                assignment.queue.push_back(task_id);
+            }
+        }
+        EventType::RobotConflict {
+            robots: pair,
+            position,
+        } => {
+            forecast.confirm(pair, event.timestamp, position);
+        }
+    }
+}
+
+/// Snaps a robot onto the end of its route and moves the route into the visited history.
+fn finish_route(
+    transform: &mut Transform,
+    path: &mut RobotPath,
+    motion: &mut RobotMotion,
+    target: Vec3,
+) {
+    transform.translation = target;
+    motion.waypoints.clear();
+    let travelled = std::mem::take(&mut path.planned);
+    path.points.extend(travelled.into_iter().skip(1));
+    if path.points.last().copied() != Some(target) {
+        path.points.push(target);
+    }
+}
+
+fn set_robot_state(
+    robots: &mut Query<RobotEventData>,
+    index: &EntityIndex,
+    robot_id: usize,
+    new_state: RobotState,
+) {
+    if let Some(Ok((.., mut state, _))) = index.robot(robot_id).map(|entity| robots.get_mut(entity))
+    {
+        *state = new_state;
+    }
+}
+
+/// Sets the fixed timestep so each tick's `SimulationClock::step` plays out at the clock's
+/// speed.
+pub fn sync_fixed_timestep(clock: Res<SimulationClock>, mut fixed: ResMut<Time<Fixed>>) {
+    if clock.is_changed() {
+        fixed.set_timestep_seconds(clock.tick_interval());
+    }
+}
+
+/// Draws robots, and the loads they carry, where their routes had them between the last
+/// two simulation ticks, so motion stays smooth whatever the tick and frame rates.
+///
+/// Only changes what is rendered: the next tick puts every robot back at its simulated
+/// position before anything reads it.
+pub fn interpolate_robot_transforms(
+    sim: Res<Simulation>,
+    clock: Res<SimulationClock>,
+    fixed: Res<Time<Fixed>>,
+    index: Res<EntityIndex>,
+    mut robots: Query<(&RobotMotion, &RobotAssignment, &RobotState, &mut Transform), With<Robot>>,
+    mut tasks: Query<&mut Transform, (With<Task>, Without<Robot>)>,
+) {
+    let shown_at = if clock.paused {
+        sim.now
+    } else {
+        let behind = (1.0 - fixed.overstep_fraction_f64()) * clock.step;
+        (sim.now - behind).max(0.0)
+    };
+    for (motion, assignment, state, mut transform) in &mut robots {
+        let Some(position) = motion.position_at(shown_at) else {
+            continue;
+        };
+        transform.translation = position;
+        if matches!(state, RobotState::Carrying | RobotState::Unloading)
+            && let Some(Ok(mut marker)) = assignment
+                .task_id
+                .and_then(|id| index.task(id))
+                .map(|task| tasks.get_mut(task))
+        {
+            marker.translation = position + Vec3::Y * 0.7;
+        }
     }
 }
 
-/// Assigns each idle robot the nearest available task and schedules travel events.
+/// Places travelling robots at their route position for the current sim time.
+pub fn update_robot_motion(
+    sim: Res<Simulation>,
+    mut robots: Query<(&RobotMotion, &mut Transform)>,
+) {
+    for (motion, mut transform) in &mut robots {
+        if let Some(position) = motion.position_at(sim.now) {
+            transform.translation = position;
+        }
+    }
+}
+
+/// Rebuilds the `SpatialIndex` from where the robots are this frame.
+pub fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    robots: Query<(Entity, &Robot, &Transform, &RobotType)>,
+) {
+    index.rebuild(
+        robots
+            .iter()
+            .map(|(entity, robot, transform, kind)| Neighbour {
+                entity,
+                id: robot.id,
+                position: transform.translation,
+                radius: kind.radius,
+            }),
+    );
+}
+
+/// Drains batteries with the length of route driven and time spent standing, and refills
+/// them while plugged in. Does nothing for scenarios without a battery model.
+pub fn update_batteries(
+    sim: Res<Simulation>,
+    config: Res<ScenarioConfig>,
+    mut robots: Query<(&RobotPath, &RobotMotion, &RobotState, &mut Battery)>,
+) {
+    let Some(model) = config.battery else {
+        return;
+    };
+    for (path, motion, state, mut battery) in &mut robots {
+        let elapsed = (sim.now - battery.last_time).max(0.0) as f32;
+        let driven = battery.drive(path, motion, sim.now);
+        let change = match state {
+            RobotState::Charging => model.charge_rate * elapsed,
+            RobotState::Idle | RobotState::Loading | RobotState::Unloading => {
+                -model.idle_drain * elapsed - model.drain_per_unit * driven
+            }
+            RobotState::TravellingEmpty | RobotState::Carrying => -model.drain_per_unit * driven,
+        };
+        battery.level = (battery.level + change).clamp(0.0, 1.0);
+        battery.lowest = battery.lowest.min(battery.level);
+        battery.last_time = sim.now;
+    }
+}
+
+/// Robot components read and written by `allocate_tasks`.
+type RobotAllocationData = (
+    &'static Robot,
+    &'static Transform,
+    &'static mut RobotAssignment,
+    &'static mut RobotPath,
+    &'static mut Battery,
+    &'static RobotType,
+);
+
+/// Hands idle robots and open tasks to the active allocator, then starts each robot
+/// without a current task on the next task in its queue.
+///
+/// Awards with a delay travel as `AwardTask` events and only reach the robot's queue once
+/// delivered; the task is reserved for the winner in the meantime. Robots without work
+/// whose battery is below the scenario's threshold are sent to charge instead.
+///
+/// Every route is reserved in the `ReservationTable` as it is scheduled. With the
+/// reservation planner, a job that cannot be planned around earlier reservations goes back
+/// to the pool, and idle robots parked in its way are sent home.
+#[allow(clippy::too_many_arguments)]
 pub fn allocate_tasks(
     mut sim: ResMut<Simulation>,
-    mut robots: Query<(Entity, &Robot, &Transform, &mut RobotAssignment)>,
-    mut tasks: Query<(Entity, &mut Task, &Transform)>,
+    mut allocator: ResMut<ActiveAllocator>,
+    planner: Res<ActivePlanner>,
+    mut reservations: ResMut<ReservationTable>,
+    config: Res<ScenarioConfig>,
+    map: Res<WarehouseMap>,
+    index: Res<EntityIndex>,
+    mut robots: Query<RobotAllocationData>,
+    motions: Query<&RobotMotion>,
+    mut tasks: Query<(&mut Task, &Transform)>,
+    stations: Query<(&ChargingStation, &Transform)>,
 ) {
     let now = sim.now;
+    send_to_chargers(
+        &mut sim,
+        &mut reservations,
+        planner.kind,
+        &config,
+        &map,
+        &index,
+        &mut robots,
+        &stations,
+    );
+
+    // Whether some robot in the fleet, busy or not, could still take a task.
+    let fleet: Vec<(f32, f32)> = robots
+        .iter()
+        .map(|(.., kind)| (kind.payload, kind.radius))
+        .collect();
+    let serviceable = |task: &Task| {
+        fleet
+            .iter()
+            .any(|&(payload, radius)| task.load <= payload && radius < task.max_radius)
+    };
+    for (mut task, _) in &mut tasks {
+        if task.is_open() && !serviceable(&task) {
+            task.unreachable = true;
+        }
+    }
+
+    // Tasks wait for their prerequisites; one that can never be done holds its dependents
+    // back for good, so they are given up on too. Chains resolve over successive frames.
+    // Only prerequisites of open tasks are collected, as finished tasks pile up over a shift.
+    let required: HashSet<usize> = tasks
+        .iter()
+        .filter(|(task, _)| task.is_open())
+        .flat_map(|(task, _)| task.requires.iter().copied())
+        .collect();
+    let completed: HashSet<usize> = tasks
+        .iter()
+        .filter(|(task, _)| task.completed && required.contains(&task.id))
+        .map(|(task, _)| task.id)
+        .collect();
+    let abandoned: HashSet<usize> = tasks
+        .iter()
+        .filter(|(task, _)| task.unreachable && required.contains(&task.id))
+        .map(|(task, _)| task.id)
+        .collect();
+    for (mut task, _) in &mut tasks {
+        if task.is_open() && task.requires.iter().any(|id| abandoned.contains(id)) {
+            task.unreachable = true;
+        }
+    }
+    let released = |task: &Task| task.requires.iter().all(|id| completed.contains(id));
+
+    // Low robots waiting for a free charger take no new work either.
+    let needs_charge = |battery: &Battery| {
+        battery.station.is_some() || config.battery.is_some_and(|b| battery.level < b.threshold)
+    };
+
+    // What is left of a robot's current route, such as a reserved way home.
+    let committed = |robot_id: usize| {
+        let motion = index
+            .robot(robot_id)
+            .and_then(|entity| motions.get(entity).ok());
+        motion.map_or(0.0, |motion| {
+            let end = motion.waypoints.last().map_or(now, |&(t, _)| t);
+            motion.distance_between(now, end)
+        })
+    };
+
+    // Sorted by id so the allocator sees the same input regardless of ECS iteration order.
+    let mut idle: Vec<IdleRobot> = robots
+        .iter()
+        .filter(|(_, _, assignment, _, battery, _)| assignment.is_idle() && !needs_charge(battery))
+        .map(|(robot, transform, _, _, _, kind)| IdleRobot {
+            id: robot.id,
+            position: transform.translation,
+            payload: kind.payload,
+            radius: kind.radius,
+            committed: committed(robot.id),
+        })
+        .collect();
+    let mut open: Vec<OpenTask> = tasks
+        .iter()
+        .filter(|(task, _)| task.is_open() && released(task))
+        .map(|(task, transform)| OpenTask {
+            id: task.id,
+            position: transform.translation,
+            dropoff: task.dropoff,
+            load: task.load,
+            max_radius: task.max_radius,
+            urgency: allocator.weights.urgency(task.priority, task.due, now),
+        })
+        .collect();
+    idle.sort_by_key(|robot| robot.id);
+    open.sort_by_key(|task| task.id);
+
+    let assignments = if idle.is_empty() || open.is_empty() {
+        Vec::new()
+    } else {
+        allocator.allocator.allocate(&idle, &open, now)
+    };
 
-    // Iterate idle robots and greedily allocate the nearest unassigned task.
-    for (_, robot, transform, mut assignment) in &mut robots {
+    for Assignment {
+        robot_id,
+        task_id,
+        delay,
+    } in assignments
+    {
+        let Some(Ok((robot, _, mut assignment, ..))) =
+            index.robot(robot_id).map(|entity| robots.get_mut(entity))
+        else {
+            continue;
+        };
+        let Some(Ok((mut task, _))) = index.task(task_id).map(|entity| tasks.get_mut(entity))
+        else {
+            continue;
+        };
+        if !task.is_open() {
+            continue;
+        }
+
+        task.assigned_to = Some(robot.id);
+        if delay > 0.0 {
+            assignment.pending_awards += 1;
+            sim.schedule(Event {
+                timestamp: now + delay,
+                event_type: EventType::AwardTask { robot_id, task_id },
+            });
+        } else {
+            assignment.queue.push_back(task_id);
+        }
+    }
+
+    // Robots without a current task take the next queued task whose route exists at all.
+    let mut ready = Vec::new();
+    for (robot, transform, mut assignment, _, _, kind) in &mut robots {
         if assignment.task_id.is_some() {
             continue;
         }
 
-        let mut best_task_entity = None;
-        let mut best_task_id = 0usize;
-        let mut best_task_pos = Vec3::ZERO;
-        let mut best_dist_sq = f32::MAX;
+        while let Some(task_id) = assignment.queue.pop_front() {
+            let Some(Ok((mut task, task_transform))) =
+                index.task(task_id).map(|entity| tasks.get_mut(entity))
+            else {
+                continue;
+            };
+            if task.completed || task.unreachable {
+                continue;
+            }
+
+            let start = transform.translation;
+            let Some(independent) = plan_job(&map, kind, start, now, &task, task_transform) else {
+                // Only narrower robots are offered the task from now on; if there are none,
+                // it would otherwise be offered again every frame. The robot moves on to its
+                // next queued task, or is reconsidered next frame.
+                task.max_radius = task.max_radius.min(kind.radius);
+                task.unreachable = !serviceable(&task);
+                task.assigned_to = None;
+                continue;
+            };
+            ready.push(ReadyJob {
+                robot_id: robot.id,
+                task_id,
+                home: robot.home,
+                start,
+                radius: kind.radius,
+                speed: kind.max_speed,
+                stops: job_stops(&task, task_transform),
+                independent,
+            });
+            break;
+        }
+    }
+
+    // Joint planners route every robot starting now in one search; should that find no
+    // plan within its budget, the robots are planned one at a time like `Reservation`.
+    ready.sort_by_key(|job| job.robot_id);
+    let mut joint = match planner.kind {
+        PlannerKind::Cbs => plan_jobs_jointly(&reservations, &map, &ready, now, 1.0),
+        PlannerKind::Ecbs => {
+            plan_jobs_jointly(&reservations, &map, &ready, now, planner.ecbs_weight)
+        }
+        PlannerKind::Independent | PlannerKind::Reservation => None,
+    }
+    .map(Vec::into_iter);
 
-        for (task_entity, task, task_transform) in &mut tasks {
-            if task.completed || task.assigned_to.is_some() {
+    // Routes the reserving planners could not get through yet, to clear for a later try.
+    let mut blocked: Vec<(usize, f32, Vec<Vec3>)> = Vec::new();
+    for job in ready {
+        let plan = match (planner.kind, joint.as_mut()) {
+            (PlannerKind::Independent, _) => Ok(job.independent),
+            (_, Some(plans)) => plans.next().ok_or(job.independent),
+            (_, None) => reserve_job(&reservations, &map, &job, now).ok_or(job.independent),
+        };
+        let Some(Ok((mut task, _))) = index.task(job.task_id).map(|entity| tasks.get_mut(entity))
+        else {
+            continue;
+        };
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(independent) => {
+                // Reachable, but not past the robots in the way right now.
+                blocked.push((job.robot_id, job.radius, independent.points));
+                task.assigned_to = None;
                 continue;
             }
+        };
+        let Some(Ok((_, _, mut assignment, mut path, ..))) = index
+            .robot(job.robot_id)
+            .map(|entity| robots.get_mut(entity))
+        else {
+            continue;
+        };
 
-            let task_pos = task_transform.translation;
-            let dist_sq = transform.translation.distance_squared(task_pos);
-            if dist_sq < best_dist_sq {
-                best_dist_sq = dist_sq;
-                best_task_entity = Some(task_entity);
-                best_task_id = task.id;
-                best_task_pos = task_pos;
+        assignment.task_id = Some(task.id);
+        path.planned = plan.points;
+        let route = schedule_job(&mut sim, job.robot_id, &task, plan.legs, plan.onward);
+        reservations.reserve(job.robot_id, job.radius, &route);
+    }
+
+    clear_the_way(
+        &mut sim,
+        &mut reservations,
+        &map,
+        &index,
+        &mut robots,
+        &blocked,
+    );
+}
+
+/// Sends idle robots parked on the routes in `blocked` back to their spawn positions, so
+/// the robots that could not plan past them get through on a later try. Robots whose home
+/// is in the way too stay where they are.
+fn clear_the_way(
+    sim: &mut Simulation,
+    reservations: &mut ReservationTable,
+    map: &WarehouseMap,
+    index: &EntityIndex,
+    robots: &mut Query<RobotAllocationData>,
+    blocked: &[(usize, f32, Vec<Vec3>)],
+) {
+    let now = sim.now;
+    for (robot_id, radius, route) in blocked {
+        for parked_id in reservations.parked_along(*robot_id, route, *radius, now) {
+            let Some(Ok((robot, transform, mut assignment, mut path, battery, kind))) =
+                index.robot(parked_id).map(|entity| robots.get_mut(entity))
+            else {
+                continue;
+            };
+            let home = robot.home;
+            if !assignment.is_idle()
+                || battery.station.is_some()
+                || blocks_route(home.xz(), kind.radius, route, *radius)
+            {
+                continue;
             }
+            let Some(mut legs) = plan_reserved(
+                reservations,
+                map,
+                parked_id,
+                kind.radius,
+                kind.max_speed,
+                transform.translation,
+                now,
+                &[(home, 0.0)],
+            ) else {
+                continue;
+            };
+            let timed = legs.remove(0);
+            let arrival = timed[timed.len() - 1].0;
+
+            assignment.repositioning = true;
+            path.planned = timed.iter().map(|&(_, point)| point).collect();
+            reservations.reserve(parked_id, kind.radius, &timed);
+            sim.schedule(Event {
+                timestamp: now,
+                event_type: EventType::DepartRobot {
+                    robot_id: parked_id,
+                    route: timed,
+                },
+            });
+            sim.schedule(Event {
+                timestamp: arrival,
+                event_type: EventType::MoveRobot {
+                    robot_id: parked_id,
+                    target: home,
+                    task_id: None,
+                },
+            });
         }
+    }
+}
 
-        let Some(task_entity) = best_task_entity else {
+/// Sends each robot without work whose battery is below the threshold to the nearest free
+/// charging station, scheduling its departure, `ChargeStart` on arrival and `ChargeEnd` once
+/// the battery would be full. Robots with no free, reachable station wait and try again, as
+/// do robots the reservation planner cannot get there yet.
+#[allow(clippy::too_many_arguments)]
+fn send_to_chargers(
+    sim: &mut Simulation,
+    reservations: &mut ReservationTable,
+    planner: PlannerKind,
+    config: &ScenarioConfig,
+    map: &WarehouseMap,
+    index: &EntityIndex,
+    robots: &mut Query<RobotAllocationData>,
+    stations: &Query<(&ChargingStation, &Transform)>,
+) {
+    let Some(model) = config.battery else {
+        return;
+    };
+    let now = sim.now;
+
+    let mut taken: HashSet<usize> = robots
+        .iter()
+        .filter_map(|(_, _, _, _, battery, _)| battery.station)
+        .collect();
+    let mut low: Vec<(usize, Vec3, RobotType)> = robots
+        .iter()
+        .filter(|(_, _, assignment, _, battery, _)| {
+            assignment.is_idle() && battery.station.is_none() && battery.level < model.threshold
+        })
+        .map(|(robot, transform, .., kind)| (robot.id, transform.translation, kind.clone()))
+        .collect();
+    low.sort_by_key(|(id, ..)| *id);
+
+    for (robot_id, position, kind) in low {
+        let mut free: Vec<(usize, Vec3)> = stations
+            .iter()
+            .filter(|(station, _)| !taken.contains(&station.id))
+            .map(|(station, transform)| {
+                (
+                    station.id,
+                    Vec3::new(transform.translation.x, 0.5, transform.translation.z),
+                )
+            })
+            .collect();
+        free.sort_by(|a, b| {
+            position
+                .distance_squared(a.1)
+                .total_cmp(&position.distance_squared(b.1))
+                .then(a.0.cmp(&b.0))
+        });
+
+        let Some((station_id, target, route)) = free.into_iter().find_map(|(id, target)| {
+            plan_path(map, position, target, kind.radius).map(|route| (id, target, route))
+        }) else {
+            continue;
+        };
+        let Some(Ok((_, _, _, mut path, mut battery, _))) =
+            index.robot(robot_id).map(|entity| robots.get_mut(entity))
+        else {
             continue;
         };
 
-        assignment.task_id = Some(best_task_id);
-        if let Ok((_, mut task, _)) = tasks.get_mut(task_entity) {
-            task.assigned_to = Some(robot.id);
-        }
+        let timed = match planner {
+            PlannerKind::Independent => {
+                time_route(&route, now, leg_time(&kind, path_length(&route)), &kind)
+            }
+            // A single robot is routed with SIPP under every reserving planner.
+            PlannerKind::Reservation | PlannerKind::Cbs | PlannerKind::Ecbs => {
+                let stops = [(target, 0.0)];
+                let reserved = plan_reserved(
+                    reservations,
+                    map,
+                    robot_id,
+                    kind.radius,
+                    kind.max_speed,
+                    position,
+                    now,
+                    &stops,
+                );
+                match reserved {
+                    Some(mut legs) => legs.remove(0),
+                    None => continue,
+                }
+            }
+        };
+        let route: Vec<Vec3> = timed.iter().map(|&(_, point)| point).collect();
+        let length = path_length(&route);
+        let arrival = timed[timed.len() - 1].0;
+        let level_on_arrival = (battery.level - model.drain_per_unit * length).max(0.0);
+        let charge_time = ((1.0 - level_on_arrival) / model.charge_rate) as f64;
 
-        let travel_time = (best_dist_sq.sqrt() / 4.0).max(0.5) as f64;
-        // Queue a move event instead of teleporting immediately.
+        taken.insert(station_id);
+        battery.station = Some(station_id);
+        reservations.reserve(robot_id, kind.radius, &timed);
         sim.schedule(Event {
-            timestamp: now + travel_time,
-            event_type: EventType::MoveRobot {
-                robot_id: robot.id,
-                target: Vec3::new(best_task_pos.x, 0.5, best_task_pos.z),
-                task_id: Some(best_task_id),
+            timestamp: now,
+            event_type: EventType::DepartRobot {
+                robot_id,
+                route: timed,
             },
         });
+        sim.schedule(Event {
+            timestamp: arrival,
+            event_type: EventType::ChargeStart { robot_id, target },
+        });
+        sim.schedule(Event {
+            timestamp: arrival + charge_time,
+            event_type: EventType::ChargeEnd { robot_id },
+        });
+        path.planned = route;
     }
 }
 
-/// Draws robot path trails using debug gizmo lines.
-pub fn draw_robot_paths(mut gizmos: Gizmos, query: Query<&RobotPath>) {
-    for path in &query {
-        if path.points.len() < 2 {
+/// A job a robot is about to start, with a route that exists on the static map.
+struct ReadyJob {
+    robot_id: usize,
+    task_id: usize,
+    home: Vec3,
+    start: Vec3,
+    radius: f32,
+    speed: f32,
+    /// Pickup and dropoff with their service times.
+    stops: Vec<(Vec3, f64)>,
+    /// Route ignoring the other robots.
+    independent: JobPlan,
+}
+
+/// A planned task route: one or two timed legs.
+struct JobPlan {
+    /// Every waypoint from the robot to the final stop, for drawing.
+    points: Vec<Vec3>,
+    /// Robot → pickup, then pickup → dropoff if the task has one, as `(sim time, position)`
+    /// waypoints; the second leg starts once loading is done.
+    legs: Vec<Vec<(f64, Vec3)>>,
+    /// Reserved way home after the last stop, empty for the independent planner.
+    onward: Vec<(f64, Vec3)>,
+}
+
+/// The task's pickup and, if any, dropoff on the robots' driving plane, each with the
+/// service time spent there.
+fn job_stops(task: &Task, marker: &Transform) -> Vec<(Vec3, f64)> {
+    let floor = |point: Vec3| Vec3::new(point.x, 0.5, point.z);
+    let mut stops = vec![(floor(marker.translation), task.pickup_service)];
+    stops.extend(
+        task.dropoff
+            .map(|dropoff| (floor(dropoff), task.dropoff_service)),
+    );
+    stops
+}
+
+/// Plans the robot's route to the task's pickup and on to its dropoff, departing at `now`
+/// and ignoring other robots.
+fn plan_job(
+    map: &WarehouseMap,
+    kind: &RobotType,
+    start: Vec3,
+    now: f64,
+    task: &Task,
+    marker: &Transform,
+) -> Option<JobPlan> {
+    let stops = job_stops(task, marker);
+    let mut points = vec![start];
+    let mut legs = Vec::with_capacity(stops.len());
+    let (mut from, mut t) = (start, now);
+    for (stop, service) in stops {
+        let route = plan_path(map, from, stop, kind.radius)?;
+        let travel_time = leg_time(kind, path_length(&route));
+        points.extend(route.iter().skip(1));
+        legs.push(time_route(&route, t, travel_time, kind));
+        t += travel_time + service;
+        from = stop;
+    }
+    Some(JobPlan {
+        points,
+        legs,
+        onward: Vec::new(),
+    })
+}
+
+impl JobPlan {
+    /// Splits reserved legs into the task's legs and the way home after them.
+    fn from_reserved(start: Vec3, mut legs: Vec<Vec<(f64, Vec3)>>) -> Self {
+        let onward = legs.pop().unwrap_or_default();
+        let mut points = vec![start];
+        for leg in &legs {
+            points.extend(leg.iter().skip(1).map(|&(_, point)| point));
+        }
+        Self {
+            points,
+            legs,
+            onward,
+        }
+    }
+}
+
+/// Plans the job around the routes other robots have reserved, waiting where needed.
+///
+/// The plan ends with the way back to the robot's home, since a robot may only claim a
+/// spot indefinitely where it never blocks another: parked on a shared dropoff it would
+/// hold up every later delivery there.
+fn reserve_job(
+    reservations: &ReservationTable,
+    map: &WarehouseMap,
+    job: &ReadyJob,
+    now: f64,
+) -> Option<JobPlan> {
+    let mut stops = job.stops.clone();
+    stops.push((job.home, 0.0));
+    let legs = plan_reserved(
+        reservations,
+        map,
+        job.robot_id,
+        job.radius,
+        job.speed,
+        job.start,
+        now,
+        &stops,
+    )?;
+    Some(JobPlan::from_reserved(job.start, legs))
+}
+
+/// Plans every ready job in one CBS search (ECBS with a `weight` above 1), each ending at
+/// the robot's home as in `reserve_job`. All robots move at the slowest one's speed.
+fn plan_jobs_jointly(
+    reservations: &ReservationTable,
+    map: &WarehouseMap,
+    ready: &[ReadyJob],
+    now: f64,
+    weight: f32,
+) -> Option<Vec<JobPlan>> {
+    let agents: Vec<Agent> = ready
+        .iter()
+        .map(|job| Agent {
+            robot_id: job.robot_id,
+            radius: job.radius,
+            start: job.start,
+            stops: job.stops.iter().copied().chain([(job.home, 0.0)]).collect(),
+        })
+        .collect();
+    let speed = ready
+        .iter()
+        .map(|job| job.speed)
+        .fold(f32::INFINITY, f32::min);
+    let plans = plan_joint(reservations, map, &agents, speed, now, weight)?;
+    Some(
+        plans
+            .into_iter()
+            .zip(ready)
+            .map(|(legs, job)| JobPlan::from_reserved(job.start, legs))
+            .collect(),
+    )
+}
+
+/// Schedules the events of a job and returns the full timed route it drives.
+///
+/// The robot departs once along a route that holds still at the pickup while loading;
+/// loading and unloading events mark the stops, and `MoveRobot` completes the task after the
+/// last one. Service phases of zero length get no start event. The route continues along
+/// `onward`, if given, after the task is done.
+fn schedule_job(
+    sim: &mut Simulation,
+    robot_id: usize,
+    task: &Task,
+    legs: Vec<Vec<(f64, Vec3)>>,
+    onward: Vec<(f64, Vec3)>,
+) -> Vec<(f64, Vec3)> {
+    let task_id = task.id;
+    let depart = legs
+        .first()
+        .and_then(|leg| leg.first())
+        .map_or(sim.now, |&(t, _)| t);
+    let mut timed_route: Vec<(f64, Vec3)> = Vec::new();
+    let mut follow_ups = Vec::new();
+
+    // A later leg starts at the previous stop, which the route already reaches; it is only
+    // kept when the robot stays on after the service time.
+    let after = |route: &[(f64, Vec3)], point: &(f64, Vec3)| {
+        route.last().is_none_or(|&(last, _)| point.0 > last)
+    };
+    for (index, leg) in legs.into_iter().enumerate() {
+        let Some(&(mut t, stop)) = leg.last() else {
+            continue;
+        };
+        for point in leg {
+            if after(&timed_route, &point) {
+                timed_route.push(point);
+            }
+        }
+
+        let pickup = index == 0;
+        let service = if pickup {
+            task.pickup_service
+        } else {
+            task.dropoff_service
+        };
+        if service > 0.0 {
+            follow_ups.push(Event {
+                timestamp: t,
+                event_type: if pickup {
+                    EventType::StartLoading { robot_id }
+                } else {
+                    EventType::StartUnloading { robot_id }
+                },
+            });
+            t += service;
+            timed_route.push((t, stop));
+        }
+
+        if pickup && task.dropoff.is_some() {
+            follow_ups.push(Event {
+                timestamp: t,
+                event_type: EventType::FinishLoading { robot_id, task_id },
+            });
+        } else {
+            follow_ups.push(Event {
+                timestamp: t,
+                event_type: EventType::MoveRobot {
+                    robot_id,
+                    target: stop,
+                    task_id: Some(task_id),
+                },
+            });
+        }
+    }
+
+    for point in onward {
+        if after(&timed_route, &point) {
+            timed_route.push(point);
+        }
+    }
+
+    // Depart now and finish after the last stop; positions in between are interpolated.
+    sim.schedule(Event {
+        timestamp: depart,
+        event_type: EventType::DepartRobot {
+            robot_id,
+            route: timed_route.clone(),
+        },
+    });
+    for event in follow_ups {
+        sim.schedule(event);
+    }
+    timed_route
+}
+
+/// Seconds a robot of `kind` needs to drive a leg of `length`, stopping at both ends.
+///
+/// Every leg takes at least `MIN_LEG_TIME`, so a job's events never share a timestamp.
+fn leg_time(kind: &RobotType, length: f32) -> f64 {
+    kind.travel_time(length).max(MIN_LEG_TIME as f32) as f64
+}
+
+/// Distance between the extra points sampled while a robot speeds up or brakes.
+const RAMP_SAMPLE_SPACING: f32 = 0.5;
+
+/// Times each point of the route by the robot type's speed profile, stretched to take
+/// `travel_time` in total.
+///
+/// Types with an acceleration get extra points along every segment so that the linear
+/// interpolation in `RobotMotion` follows the ramps instead of an average speed.
+fn time_route(route: &[Vec3], depart: f64, travel_time: f64, kind: &RobotType) -> Vec<(f64, Vec3)> {
+    let total = path_length(route);
+    let profile_time = kind.travel_time(total).max(f32::EPSILON) as f64;
+    let timed_at =
+        |distance: f32| depart + travel_time * kind.time_at(distance, total) as f64 / profile_time;
+
+    let mut timed = Vec::with_capacity(route.len());
+    let mut covered = 0.0;
+    for (i, &point) in route.iter().enumerate() {
+        if i > 0 {
+            let from = route[i - 1];
+            let length = from.distance(point);
+            if kind.acceleration.is_some() {
+                let samples = (length / RAMP_SAMPLE_SPACING).ceil() as usize;
+                for step in 1..samples {
+                    let fraction = step as f32 / samples as f32;
+                    timed.push((
+                        timed_at(covered + length * fraction),
+                        from.lerp(point, fraction),
+                    ));
+                }
+            }
+            covered += length;
+        }
+        timed.push((timed_at(covered), point));
+    }
+    timed
+}
+
+/// Keeps the marker of a task being carried or unloaded on top of its robot.
+pub fn carry_task_markers(
+    index: Res<EntityIndex>,
+    robots: Query<(&Transform, &RobotAssignment, &RobotState), With<Robot>>,
+    mut tasks: Query<(&Task, &mut Transform), Without<Robot>>,
+) {
+    for (robot_transform, assignment, state) in &robots {
+        if !matches!(state, RobotState::Carrying | RobotState::Unloading) {
+            continue;
+        }
+        let Some(task_id) = assignment.task_id else {
+            continue;
+        };
+        let marker = index.task(task_id).map(|entity| tasks.get_mut(entity));
+        if let Some(Ok((_, mut transform))) = marker {
+            transform.translation = robot_transform.translation + Vec3::Y * 0.7;
+        }
+    }
+}
+
+/// Links each undelivered pickup-and-delivery task to its dropoff with a gizmo line.
+pub fn draw_task_dropoffs(mut gizmos: Gizmos, tasks: Query<(&Task, &Transform)>) {
+    let color = Color::srgba(0.9, 0.3, 0.2, 0.5);
+    for (task, transform) in &tasks {
+        let Some(dropoff) = task.dropoff else {
+            continue;
+        };
+        if task.completed {
+            continue;
+        }
+        let dropoff = Vec3::new(dropoff.x, 0.03, dropoff.z);
+        gizmos.line(
+            Vec3::new(transform.translation.x, 0.03, transform.translation.z),
+            dropoff,
+            color,
+        );
+        gizmos.circle(
+            Isometry3d::new(dropoff, Quat::from_rotation_x(FRAC_PI_2)),
+            0.35,
+            color,
+        );
+    }
+}
+
+/// Draws an arrow from each prerequisite to the task waiting on it, until that task is done;
+/// arrows from completed prerequisites are faded.
+pub fn draw_task_dependencies(
+    mut gizmos: Gizmos,
+    index: Res<EntityIndex>,
+    tasks: Query<(&Task, &Transform)>,
+) {
+    for (task, transform) in &tasks {
+        if task.completed {
             continue;
         }
+        for &required in &task.requires {
+            let Some(Ok((prerequisite, at))) = index.task(required).map(|entity| tasks.get(entity))
+            else {
+                continue;
+            };
+            let (done, from) = (prerequisite.completed, at.translation);
+            let color = if done {
+                Color::srgba(0.6, 0.6, 0.6, 0.3)
+            } else {
+                Color::srgb(0.95, 0.75, 0.1)
+            };
+            let lift = Vec3::Y * 0.4;
+            gizmos.arrow(from + lift, transform.translation + lift, color);
+        }
+    }
+}
 
+/// Draws a charge bar above each robot: green when full, red when flat, blue while charging.
+pub fn draw_battery_bars(
+    mut gizmos: Gizmos,
+    config: Res<ScenarioConfig>,
+    robots: Query<(&Transform, &Battery, &RobotState)>,
+) {
+    if config.battery.is_none() {
+        return;
+    }
+    for (transform, battery, state) in &robots {
+        let left = transform.translation + Vec3::new(-0.5, 0.9, 0.0);
+        let right = left + Vec3::X;
+        let filled = left.lerp(right, battery.level);
+        let color = if *state == RobotState::Charging {
+            Color::srgb(0.3, 0.6, 1.0)
+        } else {
+            Color::srgb(1.0 - battery.level, battery.level, 0.1)
+        };
+        gizmos.line(filled, right, Color::srgba(0.2, 0.2, 0.2, 0.6));
+        gizmos.line(left, filled, color);
+    }
+}
+
+/// Draws robot path trails using debug gizmo lines.
+pub fn draw_robot_paths(mut gizmos: Gizmos, query: Query<&RobotPath>) {
+    for path in &query {
         for segment in path.points.windows(2) {
             let a = segment[0] + Vec3::Y * 0.05;
             let b = segment[1] + Vec3::Y * 0.05;
             gizmos.line(a, b, Color::srgb(0.1, 0.7, 1.0));
         }
+
+        // Route still ahead of the robot, drawn fainter and slightly higher.
+        for segment in path.planned.windows(2) {
+            let a = segment[0] + Vec3::Y * 0.08;
+            let b = segment[1] + Vec3::Y * 0.08;
+            gizmos.line(a, b, Color::srgba(1.0, 0.85, 0.2, 0.6));
+        }
     }
 }
 
-/// Detects robot-robot conflicts based on a fixed distance threshold.
-pub fn detect_conflicts(mut robots: Query<(Entity, &Transform, &mut CollisionState), With<Robot>>) {
-    let robot_positions: Vec<(Entity, Vec3)> = robots
+/// Robot components read and written while robots yield to each other.
+type RobotTrafficData = (
+    &'static Robot,
+    &'static Transform,
+    &'static RobotType,
+    &'static mut RobotAssignment,
+    &'static mut RobotPath,
+    &'static mut RobotMotion,
+    &'static mut RobotState,
+    &'static mut Battery,
+    &'static mut TrafficState,
+);
+
+/// Holds robots whose route runs into another robot until the way ahead is clear, and
+/// sends idle robots standing in someone's way aside.
+///
+/// Only active with a deadlock policy and independently planned routes; the other
+/// planners keep routes apart by construction. A hold delays the rest of the robot's
+/// route and its route events alike.
+#[allow(clippy::too_many_arguments)]
+pub fn yield_to_robots(
+    mut sim: ResMut<Simulation>,
+    traffic: Res<TrafficControl>,
+    planner: Res<ActivePlanner>,
+    map: Res<WarehouseMap>,
+    index: Res<SpatialIndex>,
+    entities: Res<EntityIndex>,
+    mut reservations: ResMut<ReservationTable>,
+    mut robots: Query<RobotTrafficData>,
+) {
+    if traffic.policy.is_none() || planner.kind != PlannerKind::Independent {
+        return;
+    }
+    let now = sim.now;
+    let parked: HashSet<usize> = robots
         .iter()
-        .map(|(entity, transform, _)| (entity, transform.translation))
+        .filter(|(_, _, _, assignment, _, motion, state, battery, _)| {
+            motion.waypoints.is_empty()
+                && **state == RobotState::Idle
+                && assignment.is_idle()
+                && battery.station.is_none()
+        })
+        .map(|(robot, ..)| robot.id)
         .collect();
 
-    let mut colliding_entities = HashSet::new();
-    let collision_distance_sq = 0.8_f32 * 0.8_f32;
+    // Parked robots in the way, with the heading, radius and route ahead of the waiter.
+    let mut in_the_way: Vec<(usize, Vec2, f32, Vec<Vec3>)> = Vec::new();
+    for (robot, transform, kind, _, _, mut motion, _, _, mut traffic_state) in &mut robots {
+        // Backing off.
+        if traffic_state.waiting_for.is_none() && now < traffic_state.held_until {
+            continue;
+        }
+        // A robot already holding looks ahead from where it will move on.
+        let resume = traffic_state.held_until.max(now);
+        // Only robots within reach of the lookahead can be in the way.
+        let centre = motion.position_at(resume).unwrap_or(transform.translation);
+        let range = kind.max_speed * LOOKAHEAD as f32 + kind.radius + index.max_radius();
+        let mut others: Vec<(usize, Vec3, f32)> = index
+            .within(centre, range)
+            .map(|other| (other.id, other.position, other.radius))
+            .collect();
+        others.sort_unstable_by_key(|&(id, ..)| id);
+        let Some((blocker, at)) = blocker_ahead(&motion, resume, robot.id, kind.radius, &others)
+        else {
+            // What is left of a hold whose blocker has moved on just plays out.
+            traffic_state.waiting_for = None;
+            traffic_state.held_until = traffic_state.held_until.min(now);
+            traffic_state.deadlocked = false;
+            continue;
+        };
+
+        let position = transform.translation;
+        let heading = motion.position_at(resume + LOOKAHEAD).unwrap_or(at) - position;
+        let ahead = route_ahead(&motion, now, BACKOFF_WAIT);
+        let delay = now + YIELD_HOLD - traffic_state.held_until.max(now);
+        motion.waypoints = splice_detour(
+            &motion.waypoints,
+            now,
+            &[(now, position), (now + delay, position)],
+        );
+        sim.delay_robot(robot.id, now, delay);
+        reservations.reserve(robot.id, kind.radius, &motion.waypoints);
+        traffic_state.waiting_for = Some(blocker);
+        traffic_state.held_until = now + YIELD_HOLD;
 
-    for i in 0..robot_positions.len() {
-        for j in (i + 1)..robot_positions.len() {
-            let (a_entity, a_pos) = robot_positions[i];
-            let (b_entity, b_pos) = robot_positions[j];
-            if a_pos.distance_squared(b_pos) < collision_distance_sq {
-                colliding_entities.insert(a_entity);
-                colliding_entities.insert(b_entity);
+        if parked.contains(&blocker) && !in_the_way.iter().any(|(id, ..)| *id == blocker) {
+            in_the_way.push((blocker, heading.xz(), kind.radius, ahead));
+        }
+    }
+
+    for (blocker, heading, waiter_radius, ahead) in in_the_way {
+        let Some(Ok((_, transform, kind, mut assignment, mut path, ..))) =
+            entities.robot(blocker).map(|entity| robots.get_mut(entity))
+        else {
+            continue;
+        };
+        let position = transform.translation;
+        let distance = kind.radius + waiter_radius + STEP_ASIDE_MARGIN;
+        let keeps_clear = |spot: Vec3| !blocks_route(spot.xz(), kind.radius, &ahead, waiter_radius);
+        let Some(spot) = side_step(&map, position, heading, distance, kind.radius, keeps_clear)
+        else {
+            continue;
+        };
+        move_aside(
+            &mut sim,
+            &mut reservations,
+            blocker,
+            kind,
+            &mut assignment,
+            &mut path,
+            spot,
+        );
+    }
+}
+
+/// Builds the wait-for graph of yielding robots each tick and breaks up every circular
+/// wait in it, counting and flagging the robots involved.
+///
+/// The policy is applied to the lowest-priority robot of the cycle: one without a task
+/// before one with a task, then the least important task, then the highest robot id. A
+/// replan that finds no way around falls back to backing off.
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_deadlocks(
+    mut sim: ResMut<Simulation>,
+    traffic: Res<TrafficControl>,
+    map: Res<WarehouseMap>,
+    mut reservations: ResMut<ReservationTable>,
+    mut deadlocks: ResMut<DeadlockCount>,
+    index: Res<EntityIndex>,
+    mut robots: Query<RobotTrafficData>,
+    mut tasks: Query<(&mut Task, &mut Transform), Without<Robot>>,
+) {
+    let Some(policy) = traffic.policy else {
+        return;
+    };
+    let mut graph = WaitForGraph::default();
+    for (robot, .., traffic_state) in &robots {
+        if let Some(blocker) = traffic_state.waiting_for {
+            graph.insert(robot.id, blocker);
+        }
+    }
+    let cycles = graph.cycles();
+    if cycles.is_empty() {
+        return;
+    }
+
+    let now = sim.now;
+    let others: Vec<(usize, Vec3, f32)> = robots
+        .iter()
+        .map(|(robot, transform, kind, ..)| (robot.id, transform.translation, kind.radius))
+        .collect();
+    let priority = |task_id: usize| {
+        index
+            .task(task_id)
+            .and_then(|entity| tasks.get(entity).ok())
+            .map_or(0, |(task, _)| task.priority)
+    };
+    let ranks: Vec<_> = robots
+        .iter()
+        .map(|(robot, _, _, assignment, ..)| {
+            (
+                robot.id,
+                (
+                    assignment.task_id.map(priority),
+                    std::cmp::Reverse(robot.id),
+                ),
+            )
+        })
+        .collect();
+
+    for cycle in cycles {
+        deadlocks.0 += 1;
+        // Routes the victim has to keep clear of while it backs off.
+        let waiting_routes: Vec<(usize, f32, Vec<Vec3>)> = robots
+            .iter()
+            .filter(|(robot, ..)| cycle.contains(&robot.id))
+            .filter_map(|(_, _, kind, _, _, motion, .., traffic_state)| {
+                let route = route_ahead(motion, now, BACKOFF_WAIT + YIELD_HOLD);
+                traffic_state
+                    .waiting_for
+                    .map(|blocker| (blocker, kind.radius, route))
+            })
+            .collect();
+        for (robot, .., mut traffic_state) in &mut robots {
+            if cycle.contains(&robot.id) {
+                traffic_state.deadlocked = true;
+                traffic_state.deadlock_count += 1;
+            }
+        }
+        let Some(&(victim, _)) = ranks
+            .iter()
+            .filter(|(id, _)| cycle.contains(id))
+            .min_by_key(|(_, rank)| *rank)
+        else {
+            continue;
+        };
+        let Some(Ok((
+            _,
+            transform,
+            kind,
+            mut assignment,
+            mut path,
+            mut motion,
+            mut state,
+            mut battery,
+            mut traffic_state,
+        ))) = index.robot(victim).map(|entity| robots.get_mut(entity))
+        else {
+            continue;
+        };
+        let position = transform.translation;
+        let Some(&(_, blocker_at, _)) = others
+            .iter()
+            .find(|(id, ..)| Some(*id) == traffic_state.waiting_for)
+        else {
+            continue;
+        };
+
+        traffic_state.waiting_for = None;
+        traffic_state.held_until = now;
+        settle_driven(&mut path, &mut motion, now, position);
+        let replanned = if policy == DeadlockPolicy::Replan {
+            let avoid: Vec<(Vec3, f32)> = others
+                .iter()
+                .filter(|(id, ..)| *id != victim)
+                .map(|&(_, at, radius)| (at, radius + kind.radius))
+                .collect();
+            replan_to_next_stop(&sim, &map, victim, kind, &motion, now, &avoid)
+        } else {
+            None
+        };
+        if let Some((leg, stop_time)) = replanned {
+            // The replanned leg replaces the old one up to the next stop.
+            let delay = leg[leg.len() - 1].0 - stop_time;
+            let mut waypoints = leg;
+            waypoints.extend(
+                motion
+                    .waypoints
+                    .iter()
+                    .filter(|&&(t, _)| t > stop_time)
+                    .map(|&(t, point)| (t + delay, point)),
+            );
+            motion.waypoints = waypoints;
+            sim.delay_robot(victim, stop_time, delay);
+            path.planned = route_points(&motion.waypoints);
+            reservations.reserve(victim, kind.radius, &motion.waypoints);
+            continue;
+        }
+
+        // Room is made beside the robot, clear of the routes of the robots waiting for it.
+        let waiting: Vec<&(usize, f32, Vec<Vec3>)> = waiting_routes
+            .iter()
+            .filter(|(blocker, ..)| *blocker == victim)
+            .collect();
+        let widest = waiting
+            .iter()
+            .map(|(_, radius, _)| *radius)
+            .fold(0.0, f32::max);
+        let keeps_clear = |spot: Vec3| {
+            waiting
+                .iter()
+                .all(|(_, radius, route)| !blocks_route(spot.xz(), kind.radius, route, *radius))
+        };
+        let spot = side_step(
+            &map,
+            position,
+            (blocker_at - position).xz(),
+            kind.radius + widest + STEP_ASIDE_MARGIN,
+            kind.radius,
+            keeps_clear,
+        );
+
+        if policy == DeadlockPolicy::Abort {
+            sim.cancel_robot(victim);
+            motion.waypoints.clear();
+            path.planned.clear();
+            reservations.park(victim, kind.radius, position);
+            if let Some(task_id) = assignment.task_id.take()
+                && let Some(Ok((mut task, mut marker))) =
+                    index.task(task_id).map(|entity| tasks.get_mut(entity))
+            {
+                // A load already on board is set down where the robot stopped.
+                task.assigned_to = None;
+                if task.picked_up {
+                    task.picked_up = false;
+                    marker.translation = Vec3::new(position.x, 0.25, position.z);
+                }
             }
+            assignment.repositioning = false;
+            battery.station = None;
+            *state = RobotState::Idle;
+            // Without other work to go to, the robot clears the way itself.
+            if let Some(spot) = spot
+                && assignment.is_idle()
+            {
+                let (assignment, path) = (&mut *assignment, &mut *path);
+                move_aside(
+                    &mut sim,
+                    &mut reservations,
+                    victim,
+                    kind,
+                    assignment,
+                    path,
+                    spot,
+                );
+            }
+            continue;
         }
+
+        let Some(spot) = spot else {
+            continue;
+        };
+        let step = leg_time(kind, position.distance(spot));
+        let mut detour = time_route(&[position, spot], now, step, kind);
+        detour.extend(time_route(
+            &[spot, position],
+            now + step + BACKOFF_WAIT,
+            step,
+            kind,
+        ));
+        let end = detour[detour.len() - 1].0;
+        motion.waypoints = splice_detour(&motion.waypoints, now, &detour);
+        sim.delay_robot(victim, now, end - now);
+        traffic_state.held_until = end;
+        path.planned = route_points(&motion.waypoints);
+        reservations.reserve(victim, kind.radius, &motion.waypoints);
     }
+}
+
+/// Sends a robot standing at `path`'s last point off to `spot`, to make room for others.
+fn move_aside(
+    sim: &mut Simulation,
+    reservations: &mut ReservationTable,
+    robot_id: usize,
+    kind: &RobotType,
+    assignment: &mut RobotAssignment,
+    path: &mut RobotPath,
+    spot: Vec3,
+) {
+    let now = sim.now;
+    let Some(&position) = path.points.last() else {
+        return;
+    };
+    let timed = time_route(
+        &[position, spot],
+        now,
+        leg_time(kind, position.distance(spot)),
+        kind,
+    );
+    let arrival = timed[timed.len() - 1].0;
 
-    for (entity, _, mut collision) in &mut robots {
-        collision.is_colliding = colliding_entities.contains(&entity);
+    assignment.repositioning = true;
+    path.planned = vec![position, spot];
+    reservations.reserve(robot_id, kind.radius, &timed);
+    sim.schedule(Event {
+        timestamp: now,
+        event_type: EventType::DepartRobot {
+            robot_id,
+            route: timed,
+        },
+    });
+    sim.schedule(Event {
+        timestamp: arrival,
+        event_type: EventType::MoveRobot {
+            robot_id,
+            target: spot,
+            task_id: None,
+        },
+    });
+}
+
+/// Moves the part of a robot's route driven by `now` into its visited history, leaving
+/// only the route ahead, starting at `position`, in `motion`.
+fn settle_driven(path: &mut RobotPath, motion: &mut RobotMotion, now: f64, position: Vec3) {
+    let driven = motion.waypoints.iter().skip(1).filter(|&&(t, _)| t <= now);
+    path.points.extend(driven.map(|&(_, point)| point));
+    if path.points.last() != Some(&position) {
+        path.points.push(position);
+    }
+    motion.waypoints.retain(|&(t, _)| t > now);
+    motion.waypoints.insert(0, (now, position));
+}
+
+/// A new timed leg from where the robot stands at `now` to its next stop, the point its
+/// next route event happens at, keeping out of the `avoid` discs. Returns the leg and the
+/// time the old route reached the stop.
+fn replan_to_next_stop(
+    sim: &Simulation,
+    map: &WarehouseMap,
+    robot_id: usize,
+    kind: &RobotType,
+    motion: &RobotMotion,
+    now: f64,
+    avoid: &[(Vec3, f32)],
+) -> Option<(Vec<(f64, Vec3)>, f64)> {
+    let (&(_, start), &(end, _)) = (motion.waypoints.first()?, motion.waypoints.last()?);
+    let stop_time = sim.next_robot_event(robot_id).unwrap_or(end).min(end);
+    let stop = motion.position_at(stop_time)?;
+    let route = plan_path_around(map, start, stop, kind.radius, avoid)?;
+    let leg = time_route(&route, now, leg_time(kind, path_length(&route)), kind);
+    Some((leg, stop_time))
+}
+
+/// The positions of a timed route, without repeats where the robot stands still.
+fn route_points(waypoints: &[(f64, Vec3)]) -> Vec<Vec3> {
+    let mut points: Vec<Vec3> = waypoints.iter().map(|&(_, point)| point).collect();
+    points.dedup();
+    points
+}
+
+/// Detects robot-robot conflicts where two footprints overlap, and robots driving into
+/// blocked map cells.
+pub fn detect_conflicts(
+    map: Res<WarehouseMap>,
+    index: Res<SpatialIndex>,
+    mut robots: Query<(Entity, &mut CollisionState), With<Robot>>,
+) {
+    let mut colliding_entities: HashSet<Entity> = index
+        .robots()
+        .iter()
+        .filter(|robot| map.is_blocked_at(robot.position))
+        .map(|robot| robot.entity)
+        .collect();
+
+    for (a, b) in index.overlapping_pairs() {
+        colliding_entities.insert(a.entity);
+        colliding_entities.insert(b.entity);
+    }
+
+    for (entity, mut collision) in &mut robots {
+        let is_colliding = colliding_entities.contains(&entity);
+        if is_colliding && !collision.is_colliding {
+            collision.conflict_count += 1;
+        }
+        collision.is_colliding = is_colliding;
+    }
+}
+
+/// Predicts where robots will run into each other along their routes, scheduling a
+/// `RobotConflict` event for each pair's next contact.
+///
+/// Unlike `detect_conflicts`, this also catches robots that pass through each other between
+/// two frames. Only pairs involving a robot whose route changed are predicted again, and
+/// only if their route bounds in the `SpatialIndex` overlap; the events of the predictions
+/// they replace are taken off the queue.
+pub fn predict_conflicts(
+    mut sim: ResMut<Simulation>,
+    mut forecast: ResMut<ConflictForecast>,
+    mut index: ResMut<SpatialIndex>,
+    entities: Res<EntityIndex>,
+    robots: Query<(&Robot, &Transform, &RobotType, Ref<RobotMotion>)>,
+) {
+    let changed: HashSet<usize> = robots
+        .iter()
+        .filter(|(.., motion)| motion.is_changed())
+        .map(|(robot, ..)| robot.id)
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+    let stale = forecast.forget(&changed);
+    if !stale.is_empty() {
+        sim.cancel(|event| match event.event_type {
+            EventType::RobotConflict { robots, .. } => stale.contains(&robots),
+            _ => false,
+        });
+    }
+
+    let now = sim.now;
+    let track = |id: usize| {
+        let (_, transform, kind, motion) = robots.get(entities.robot(id)?).ok()?;
+        let trajectory = Trajectory {
+            motion: motion.into_inner(),
+            rest: transform.translation,
+        };
+        Some((kind.radius, trajectory))
+    };
+    let mut bounds = Vec::with_capacity(changed.len());
+    for &id in &changed {
+        if let Some((radius, trajectory)) = track(id) {
+            let area = trajectory.bounds(now, radius);
+            index.set_route(id, area);
+            bounds.push((id, area));
+        }
+    }
+
+    // In id order, and each pair once even when both robots changed.
+    let mut pairs = Vec::new();
+    for &(id, area) in &bounds {
+        for other in index.routes_overlapping(area) {
+            if other != id {
+                pairs.push((id.min(other), id.max(other)));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    for (a, b) in pairs {
+        let (Some((a_radius, a_track)), Some((b_radius, b_track))) = (track(a), track(b)) else {
+            continue;
+        };
+        let Some(conflict) = first_contact(a_track, b_track, a_radius + b_radius, now) else {
+            continue;
+        };
+        forecast.pending.insert((a, b), conflict);
+        sim.schedule(Event {
+            timestamp: conflict.time,
+            event_type: EventType::RobotConflict {
+                robots: (a, b),
+                position: conflict.position,
+            },
+        });
+    }
+}
+
+/// Marks predicted conflicts ahead with orange rings, and those of the last few seconds
+/// in red.
+pub fn draw_conflict_forecast(
+    mut gizmos: Gizmos,
+    sim: Res<Simulation>,
+    forecast: Res<ConflictForecast>,
+) {
+    let flat = Quat::from_rotation_x(FRAC_PI_2);
+    for conflict in forecast.pending.values() {
+        let at = Isometry3d::new(conflict.position.with_y(0.05), flat);
+        gizmos.circle(at, 0.4, Color::srgb(1.0, 0.6, 0.1));
+    }
+    for (_, _, conflict) in forecast.occurred.iter().rev() {
+        if sim.now - conflict.time > 3.0 {
+            break;
+        }
+        let at = Isometry3d::new(conflict.position.with_y(0.05), flat);
+        gizmos.circle(at, 0.4, Color::srgb(0.9, 0.1, 0.1));
     }
 }
 
-/// Applies a highlight material to robots currently flagged as colliding.
+/// Applies a highlight material to robots currently flagged as colliding, or as part of a
+/// deadlock.
+#[allow(clippy::type_complexity)]
 pub fn highlight_collisions(
     visuals: Res<RobotVisualMaterials>,
-    mut robots: Query<(&CollisionState, &mut MeshMaterial3d<StandardMaterial>), With<Robot>>,
+    mut robots: Query<
+        (
+            &CollisionState,
+            &TrafficState,
+            &RobotMaterial,
+            &mut MeshMaterial3d<StandardMaterial>,
+        ),
+        With<Robot>,
+    >,
 ) {
-    for (collision, mut material) in &mut robots {
-        if collision.is_colliding {
+    for (collision, traffic, own, mut material) in &mut robots {
+        if traffic.deadlocked {
+            *material = MeshMaterial3d(visuals.deadlock.clone());
+        } else if collision.is_colliding {
             *material = MeshMaterial3d(visuals.collision.clone());
         } else {
-            *material = MeshMaterial3d(visuals.normal.clone());
+            *material = MeshMaterial3d(own.0.clone());
         }
     }
 }
@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Smallest grid cell side, so tiny robots don't spread a query over many cells.
+const MIN_CELL_SIZE: f32 = 0.5;
+/// Grid cell side for route bounds, which are much larger than a robot.
+const ROUTE_CELL_SIZE: f32 = 4.0;
+
+/// A robot as stored in the index.
+#[derive(Clone, Copy, Debug)]
+pub struct Neighbour {
+    pub entity: Entity,
+    /// Robot id.
+    pub id: usize,
+    pub position: Vec3,
+    /// Footprint radius.
+    pub radius: f32,
+}
+
+#[derive(Resource, Default)]
+/// Uniform grid over the floor bucketing robots by position, for neighbour queries that
+/// would otherwise compare every robot with every other.
+///
+/// Rebuilt from the robots' `Transform`s each frame by `update_spatial_index`. Cells are
+/// twice the largest footprint radius across, so robots that overlap always sit in the
+/// same or adjacent cells.
+///
+/// The floor area each robot's route can still take it through is kept on a coarser grid,
+/// updated robot by robot as routes change, for finding robots that may meet later on.
+pub struct SpatialIndex {
+    cell_size: f32,
+    max_radius: f32,
+    entries: Vec<Neighbour>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Route bounds by robot id.
+    routes: HashMap<usize, Rect>,
+    /// Robot ids whose route bounds reach into each route cell.
+    route_cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialIndex {
+    /// Replaces the contents of the index with `robots`.
+    pub fn rebuild(&mut self, robots: impl IntoIterator<Item = Neighbour>) {
+        self.entries.clear();
+        self.entries.extend(robots);
+        self.max_radius = self.entries.iter().map(|n| n.radius).fold(0.0, f32::max);
+        self.cell_size = (self.max_radius * 2.0).max(MIN_CELL_SIZE);
+        // Keep the buckets' allocations; robots mostly stay in the cells they were in.
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+        for (index, entry) in self.entries.iter().enumerate() {
+            let cell = self.cell_of(entry.position);
+            self.cells.entry(cell).or_default().push(index);
+        }
+        self.cells.retain(|_, bucket| !bucket.is_empty());
+    }
+
+    /// Largest footprint radius among the indexed robots.
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    /// Every indexed robot, in the order it was added.
+    pub fn robots(&self) -> &[Neighbour] {
+        &self.entries
+    }
+
+    /// Robots whose centre lies within `range` of `point` on the floor.
+    pub fn within(&self, point: Vec3, range: f32) -> impl Iterator<Item = &Neighbour> {
+        let (min_x, min_z) = self.cell_of(point - Vec3::new(range, 0.0, range));
+        let (max_x, max_z) = self.cell_of(point + Vec3::new(range, 0.0, range));
+        (min_x..=max_x)
+            .flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|&index| &self.entries[index])
+            .filter(move |entry| entry.position.xz().distance_squared(point.xz()) < range * range)
+    }
+
+    /// Every pair of robots whose footprints overlap, each pair once.
+    pub fn overlapping_pairs(&self) -> Vec<(Neighbour, Neighbour)> {
+        let mut pairs = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let (x, z) = self.cell_of(entry.position);
+            let cells = (x - 1..=x + 1).flat_map(|cx| (z - 1..=z + 1).map(move |cz| (cx, cz)));
+            for bucket in cells.filter_map(|cell| self.cells.get(&cell)) {
+                for &other_index in bucket {
+                    let other = self.entries[other_index];
+                    if other_index > index && overlaps(entry, &other) {
+                        pairs.push((*entry, other));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Records the floor area robot `id`'s route can still take it through, replacing the
+    /// one recorded before.
+    pub fn set_route(&mut self, id: usize, bounds: Rect) {
+        self.remove_route(id);
+        for cell in route_cells(bounds) {
+            self.route_cells.entry(cell).or_default().push(id);
+        }
+        self.routes.insert(id, bounds);
+    }
+
+    /// Forgets robot `id`'s route bounds.
+    pub fn remove_route(&mut self, id: usize) {
+        let Some(bounds) = self.routes.remove(&id) else {
+            return;
+        };
+        for cell in route_cells(bounds) {
+            if let Some(bucket) = self.route_cells.get_mut(&cell) {
+                bucket.retain(|&other| other != id);
+                if bucket.is_empty() {
+                    self.route_cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// Ids of the robots whose route bounds overlap `bounds`, in ascending order.
+    pub fn routes_overlapping(&self, bounds: Rect) -> Vec<usize> {
+        let mut ids: Vec<usize> = route_cells(bounds)
+            .filter_map(|cell| self.route_cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|id| !self.routes[id].intersect(bounds).is_empty())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn cell_of(&self, point: Vec3) -> (i32, i32) {
+        let size = if self.cell_size > 0.0 {
+            self.cell_size
+        } else {
+            MIN_CELL_SIZE
+        };
+        (
+            (point.x / size).floor() as i32,
+            (point.z / size).floor() as i32,
+        )
+    }
+}
+
+/// The route grid cells `bounds` reaches into.
+fn route_cells(bounds: Rect) -> impl Iterator<Item = (i32, i32)> {
+    let cell = |value: f32| (value / ROUTE_CELL_SIZE).floor() as i32;
+    let (min_x, min_z) = (cell(bounds.min.x), cell(bounds.min.y));
+    let (max_x, max_z) = (cell(bounds.max.x), cell(bounds.max.y));
+    (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+}
+
+/// True if the footprints of `a` and `b` overlap.
+pub fn overlaps(a: &Neighbour, b: &Neighbour) -> bool {
+    let reach = a.radius + b.radius;
+    a.position.distance_squared(b.position) < reach * reach
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::SimRng;
+    use std::collections::BTreeSet;
+
+    fn robot(id: usize, x: f32, z: f32, radius: f32) -> Neighbour {
+        Neighbour {
+            entity: Entity::PLACEHOLDER,
+            id,
+            position: Vec3::new(x, 0.25, z),
+            radius,
+        }
+    }
+
+    /// Robot pairs as `(lower id, higher id)`.
+    type Pairs = BTreeSet<(usize, usize)>;
+
+    /// Overlapping pairs from the grid and from comparing every robot with every other.
+    fn pair_sets(robots: &[Neighbour]) -> (Pairs, Pairs) {
+        let mut index = SpatialIndex::default();
+        index.rebuild(robots.iter().copied());
+        let grid: Vec<(usize, usize)> = index
+            .overlapping_pairs()
+            .iter()
+            .map(|(a, b)| (a.id.min(b.id), a.id.max(b.id)))
+            .collect();
+        let unique: Pairs = grid.iter().copied().collect();
+        assert_eq!(
+            unique.len(),
+            grid.len(),
+            "a pair was reported twice: {grid:?}"
+        );
+
+        let mut all = Pairs::new();
+        for (i, a) in robots.iter().enumerate() {
+            for b in &robots[i + 1..] {
+                if overlaps(a, b) {
+                    all.insert((a.id.min(b.id), a.id.max(b.id)));
+                }
+            }
+        }
+        (unique, all)
+    }
+
+    #[test]
+    fn robots_straddling_cell_borders_are_paired() {
+        // Cells are 0.6 across for these radii: pairs across a border at x = 0, across the
+        // corner at (0.6, 0.6), and either side of the origin on both axes.
+        let robots = [
+            robot(0, -0.05, 0.3, 0.3),
+            robot(1, 0.05, 0.3, 0.3),
+            robot(2, 0.55, 0.55, 0.3),
+            robot(3, 0.65, 0.65, 0.3),
+            robot(4, -0.2, -0.2, 0.3),
+            robot(5, 0.2, 0.2, 0.3),
+            // Just out of reach across a border.
+            robot(6, 3.0 - 0.31, 3.0, 0.3),
+            robot(7, 3.0 + 0.3, 3.0, 0.3),
+        ];
+        let (grid, all) = pair_sets(&robots);
+        assert_eq!(grid, all);
+        assert_eq!(
+            all,
+            Pairs::from([
+                (0, 1),
+                (0, 4),
+                (0, 5),
+                (1, 2),
+                (1, 4),
+                (1, 5),
+                (2, 3),
+                (2, 5),
+                (4, 5)
+            ])
+        );
+    }
+
+    #[test]
+    fn overlapping_pairs_match_brute_force() {
+        for seed in 0..200 {
+            let mut rng = SimRng::new(seed);
+            let count = 2 + (rng.next_u64() % 60) as usize;
+            let spread = rng.range(1.0, 10.0);
+            let robots: Vec<Neighbour> = (0..count)
+                .map(|id| {
+                    let x = rng.range(-spread, spread);
+                    let z = rng.range(-spread, spread);
+                    robot(id, x, z, rng.range(0.1, 0.6))
+                })
+                .collect();
+            let (grid, all) = pair_sets(&robots);
+            assert_eq!(grid, all, "seed {seed}");
+        }
+    }
+}
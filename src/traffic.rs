@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::map::WarehouseMap;
+use crate::model::RobotMotion;
+
+/// How far ahead along its route a robot looks for robots in the way, in sim seconds.
+pub const LOOKAHEAD: f64 = 0.5;
+/// Route samples taken within `LOOKAHEAD`.
+const LOOKAHEAD_SAMPLES: usize = 4;
+/// How long a yielding robot holds position before it looks again.
+pub const YIELD_HOLD: f64 = 0.2;
+/// How long a backing-off robot waits beside its route for the others to pass.
+pub const BACKOFF_WAIT: f64 = 1.5;
+/// Gap kept on top of the footprints when a robot steps aside.
+pub const STEP_ASIDE_MARGIN: f32 = 0.1;
+
+/// How a circular wait between yielding robots is broken up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DeadlockPolicy {
+    /// The lowest-priority robot in the cycle steps off its route, waits for the others to
+    /// pass and then carries on.
+    #[default]
+    BackOff,
+    /// The lowest-priority robot plans a new way to its next stop around the robots
+    /// standing in its way.
+    Replan,
+    /// The lowest-priority robot stops and hands its task back to the pool.
+    Abort,
+}
+
+impl DeadlockPolicy {
+    /// Display name used in the headless report.
+    pub fn label(self) -> &'static str {
+        match self {
+            DeadlockPolicy::BackOff => "back off",
+            DeadlockPolicy::Replan => "replan",
+            DeadlockPolicy::Abort => "abort task",
+        }
+    }
+
+    /// Parses a policy name as given on the command line (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "back-off" | "backoff" => Some(DeadlockPolicy::BackOff),
+            "replan" => Some(DeadlockPolicy::Replan),
+            "abort" | "abort-task" => Some(DeadlockPolicy::Abort),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Copy, Default)]
+/// Whether robots yield to each other, and how deadlocks between them are resolved.
+pub struct TrafficControl {
+    /// Deadlock resolution policy; `None` leaves robots driving through each other, with
+    /// `detect_conflicts` reporting where they meet.
+    pub policy: Option<DeadlockPolicy>,
+}
+
+impl TrafficControl {
+    /// Yielding with `policy`, or no yielding at all for `None`.
+    pub fn new(policy: Option<DeadlockPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+#[derive(Resource, Default)]
+/// Number of deadlocks detected (and resolved) in the current run.
+pub struct DeadlockCount(pub u32);
+
+/// Which robot each blocked robot is waiting for.
+///
+/// Every robot waits for at most one other, so each robot has at most one outgoing edge
+/// and a deadlock is a cycle found by following those edges.
+#[derive(Default)]
+pub struct WaitForGraph {
+    edges: HashMap<usize, usize>,
+}
+
+impl WaitForGraph {
+    /// Records that `waiter` is holding position for `blocker`.
+    pub fn insert(&mut self, waiter: usize, blocker: usize) {
+        self.edges.insert(waiter, blocker);
+    }
+
+    /// Every circular wait, each listed once starting from its smallest robot id.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut starts: Vec<usize> = self.edges.keys().copied().collect();
+        starts.sort_unstable();
+
+        let mut done: HashSet<usize> = HashSet::new();
+        let mut cycles = Vec::new();
+        for start in starts {
+            let mut chain = Vec::new();
+            let mut current = start;
+            // Walk until the chain ends, reaches a robot walked before, or closes on itself.
+            while !done.contains(&current) {
+                if let Some(at) = chain.iter().position(|&id| id == current) {
+                    let mut cycle = chain[at..].to_vec();
+                    let smallest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap_or(0);
+                    cycle.rotate_left(smallest);
+                    cycles.push(cycle);
+                    break;
+                }
+                chain.push(current);
+                match self.edges.get(&current) {
+                    Some(&next) => current = next,
+                    None => break,
+                }
+            }
+            done.extend(chain);
+        }
+        cycles
+    }
+}
+
+/// The robot, among `others` as `(id, position, radius)`, that the route in `motion` runs
+/// into within `LOOKAHEAD` of moving on at `resume`: the first one the robot's footprint
+/// would overlap while getting closer to it.
+pub fn blocker_ahead(
+    motion: &RobotMotion,
+    resume: f64,
+    robot_id: usize,
+    radius: f32,
+    others: &[(usize, Vec3, f32)],
+) -> Option<(usize, Vec3)> {
+    let position = motion.position_at(resume)?;
+    for step in 1..=LOOKAHEAD_SAMPLES {
+        let at = resume + LOOKAHEAD * step as f64 / LOOKAHEAD_SAMPLES as f64;
+        let ahead = motion.position_at(at)?;
+        for &(id, other, other_radius) in others {
+            if id == robot_id {
+                continue;
+            }
+            let gap = ahead.xz().distance(other.xz());
+            if gap < radius + other_radius && gap < position.xz().distance(other.xz()) - 1e-3 {
+                return Some((id, other));
+            }
+        }
+    }
+    None
+}
+
+/// Splices `detour` into a timed route at `now`: the detour follows the waypoints already
+/// passed, and what was left of the route resumes after it, later by the detour's length.
+///
+/// The detour starts where the robot is at `now` and ends where it left the route.
+pub fn splice_detour(
+    waypoints: &[(f64, Vec3)],
+    now: f64,
+    detour: &[(f64, Vec3)],
+) -> Vec<(f64, Vec3)> {
+    let delay = detour.last().map_or(0.0, |&(t, _)| t - now);
+    let mut spliced: Vec<(f64, Vec3)> = waypoints
+        .iter()
+        .copied()
+        .filter(|&(t, _)| t <= now)
+        .collect();
+    for &(t, point) in detour {
+        if spliced
+            .last()
+            .is_none_or(|&(last_t, last)| last_t < t || last != point)
+        {
+            spliced.push((t, point));
+        }
+    }
+    spliced.extend(
+        waypoints
+            .iter()
+            .filter(|&&(t, _)| t > now)
+            .map(|&(t, point)| (t + delay, point)),
+    );
+    // Repeated holds at one spot would pile up waypoints between equal neighbours.
+    let mut kept = Vec::with_capacity(spliced.len());
+    for (i, &waypoint) in spliced.iter().enumerate() {
+        let stationary = i > 0
+            && i + 1 < spliced.len()
+            && spliced[i - 1].1 == waypoint.1
+            && spliced[i + 1].1 == waypoint.1;
+        if !stationary {
+            kept.push(waypoint);
+        }
+    }
+    kept
+}
+
+/// Where the route in `motion` takes the robot over the `span` seconds after `now`.
+pub fn route_ahead(motion: &RobotMotion, now: f64, span: f64) -> Vec<Vec3> {
+    let until = now + span;
+    let mut points: Vec<Vec3> = motion.position_at(now).into_iter().collect();
+    points.extend(
+        motion
+            .waypoints
+            .iter()
+            .filter(|&&(t, _)| t > now && t < until)
+            .map(|&(_, point)| point),
+    );
+    points.extend(motion.position_at(until));
+    points
+}
+
+/// A free spot to the side of `position`, across `heading`, for a robot of `radius` to get
+/// out of the way: `distance` away if that spot satisfies `keeps_clear`, else twice as far.
+pub fn side_step(
+    map: &WarehouseMap,
+    position: Vec3,
+    heading: Vec2,
+    distance: f32,
+    radius: f32,
+    keeps_clear: impl Fn(Vec3) -> bool,
+) -> Option<Vec3> {
+    let across = heading.try_normalize()?.perp();
+    let free = |point: Vec3| {
+        map.world_to_cell(point)
+            .is_some_and(|cell| map.has_clearance(cell, radius))
+    };
+    [1.0, 2.0].into_iter().find_map(|scale| {
+        [across, -across].into_iter().find_map(|side| {
+            let spot = position + Vec3::new(side.x, 0.0, side.y) * distance * scale;
+            // Check the way there about every half unit, the spot included.
+            let samples = (distance * scale * 2.0).ceil() as usize;
+            let reachable =
+                (1..=samples).all(|i| free(position.lerp(spot, i as f32 / samples as f32)));
+            (reachable && keeps_clear(spot)).then_some(spot)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(usize, usize)]) -> WaitForGraph {
+        let mut graph = WaitForGraph::default();
+        for &(waiter, blocker) in edges {
+            graph.insert(waiter, blocker);
+        }
+        graph
+    }
+
+    #[test]
+    fn two_robots_waiting_on_each_other() {
+        assert_eq!(graph(&[(4, 2), (2, 4)]).cycles(), [vec![2, 4]]);
+    }
+
+    #[test]
+    fn three_robot_cycles_start_at_the_smallest_id() {
+        assert_eq!(graph(&[(5, 1), (1, 3), (3, 5)]).cycles(), [vec![1, 3, 5]]);
+        // Robots queued behind the cycle are not part of it.
+        let tailed = graph(&[(9, 7), (7, 3), (3, 5), (5, 1), (1, 3)]);
+        assert_eq!(tailed.cycles(), [vec![1, 3, 5]]);
+    }
+
+    #[test]
+    fn waiting_chains_are_no_deadlock() {
+        assert!(graph(&[(0, 1), (1, 2), (2, 3), (4, 2)]).cycles().is_empty());
+        assert!(WaitForGraph::default().cycles().is_empty());
+    }
+
+    #[test]
+    fn a_robot_waiting_on_itself_is_a_cycle() {
+        assert_eq!(graph(&[(3, 3)]).cycles(), [vec![3]]);
+        // Separate deadlocks are each reported once, in order of their smallest robot.
+        let several = graph(&[(6, 6), (8, 0), (0, 8), (2, 6)]);
+        assert_eq!(several.cycles(), [vec![0, 8], vec![6]]);
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 
+use crate::allocation::ActiveAllocator;
 use crate::model::{
-    ActiveScenario, Robot, RobotVisualMaterials, ScenarioConfig, Scenario, Simulation, Task,
+    ActiveScenario, ChargingStation, Robot, Scenario, ScenarioConfig, ScenarioLibrary, SimRng,
+    Simulation, SimulationClock, SimulationSeed, Task,
 };
 use crate::simulation::spawn_scenario;
 
@@ -13,8 +15,51 @@ pub struct RestartButton;
 #[derive(Component)]
 pub struct ScenarioButton(pub Scenario);
 
+/// Marker for the button that re-seeds and restarts the current scenario.
+#[derive(Component)]
+pub struct NewSeedButton;
+
+/// Digits of a seed being typed in the window, applied with `Enter`.
+#[derive(Resource, Default)]
+pub struct SeedEntry(pub String);
+
+/// Number keys and the digit each one types.
+const DIGIT_KEYS: [(KeyCode, KeyCode, char); 10] = [
+    (KeyCode::Digit0, KeyCode::Numpad0, '0'),
+    (KeyCode::Digit1, KeyCode::Numpad1, '1'),
+    (KeyCode::Digit2, KeyCode::Numpad2, '2'),
+    (KeyCode::Digit3, KeyCode::Numpad3, '3'),
+    (KeyCode::Digit4, KeyCode::Numpad4, '4'),
+    (KeyCode::Digit5, KeyCode::Numpad5, '5'),
+    (KeyCode::Digit6, KeyCode::Numpad6, '6'),
+    (KeyCode::Digit7, KeyCode::Numpad7, '7'),
+    (KeyCode::Digit8, KeyCode::Numpad8, '8'),
+    (KeyCode::Digit9, KeyCode::Numpad9, '9'),
+];
+
+/// Marker for the button that cycles through allocation strategies.
+#[derive(Component)]
+pub struct AllocatorButton;
+
+/// Marker for the text inside the allocator button.
+#[derive(Component)]
+pub struct AllocatorLabel;
+
+/// Marks a button that drives the simulation clock.
+#[derive(Component, Clone, Copy)]
+pub enum ClockButton {
+    TogglePause,
+    Step,
+    Slower,
+    Faster,
+}
+
+/// Marker for the text showing the clock state and speed multiplier.
+#[derive(Component)]
+pub struct ClockStatusText;
+
 /// Spawns the overlay UI camera and the button row.
-pub fn setup_restart_ui(mut commands: Commands) {
+pub fn setup_restart_ui(mut commands: Commands, library: Res<ScenarioLibrary>) {
     // Render UI after the 3D camera pass.
     commands.spawn((
         Camera2d,
@@ -32,17 +77,115 @@ pub fn setup_restart_ui(mut commands: Commands) {
                 position_type: PositionType::Absolute,
                 top: px(12.0),
                 left: px(12.0),
+                right: px(12.0),
                 flex_direction: FlexDirection::Row,
+                // Wrap so loaded scenario files do not push controls off screen.
+                flex_wrap: FlexWrap::Wrap,
                 column_gap: px(8.0),
+                row_gap: px(8.0),
                 ..default()
             },
             BackgroundColor(Color::NONE),
         ))
         .with_children(|parent| {
-            spawn_scenario_button(parent, "Small", Scenario::Small);
-            spawn_scenario_button(parent, "Warehouse", Scenario::Warehouse);
-            spawn_scenario_button(parent, "Stress Test", Scenario::StressTest);
+            for scenario in library.scenarios() {
+                spawn_scenario_button(parent, library.label(scenario), scenario);
+            }
             spawn_restart_button(parent);
+            parent
+                .spawn((
+                    Button,
+                    NewSeedButton,
+                    Node {
+                        min_height: px(36.0),
+                        padding: UiRect::axes(px(12.0), px(8.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.18, 0.45, 0.85)),
+                    ZIndex(10),
+                ))
+                .with_children(|btn| {
+                    btn.spawn((
+                        Text::new("New Seed"),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+                });
+            parent
+                .spawn((
+                    Button,
+                    AllocatorButton,
+                    Node {
+                        min_height: px(36.0),
+                        padding: UiRect::axes(px(12.0), px(8.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+                    ZIndex(10),
+                ))
+                .with_children(|btn| {
+                    btn.spawn((
+                        Text::new(""),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                        AllocatorLabel,
+                    ));
+                });
+            spawn_clock_button(parent, "Pause/Resume", ClockButton::TogglePause);
+            spawn_clock_button(parent, "Step", ClockButton::Step);
+            spawn_clock_button(parent, "Slower", ClockButton::Slower);
+            spawn_clock_button(parent, "Faster", ClockButton::Faster);
+            parent.spawn((
+                Node {
+                    min_height: px(36.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                Text::new(""),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                ClockStatusText,
+            ));
+        });
+}
+
+fn spawn_clock_button(parent: &mut ChildSpawnerCommands, label: &str, action: ClockButton) {
+    parent
+        .spawn((
+            Button,
+            action,
+            Node {
+                min_height: px(36.0),
+                padding: UiRect::axes(px(12.0), px(8.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.25, 0.25, 0.25)),
+            ZIndex(10),
+        ))
+        .with_children(|btn| {
+            btn.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
         });
 }
 
@@ -64,7 +207,10 @@ fn spawn_scenario_button(parent: &mut ChildSpawnerCommands, label: &str, scenari
         .with_children(|btn| {
             btn.spawn((
                 Text::new(label),
-                TextFont { font_size: 16.0, ..default() },
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
                 TextColor(Color::WHITE),
             ));
         });
@@ -89,40 +235,43 @@ fn spawn_restart_button(parent: &mut ChildSpawnerCommands) {
         .with_children(|btn| {
             btn.spawn((
                 Text::new("Restart Simulation"),
-                TextFont { font_size: 16.0, ..default() },
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
                 TextColor(Color::WHITE),
             ));
         });
 }
 
-/// Despawns all robots and tasks, then respawns from `config`.
-#[allow(clippy::too_many_arguments)]
+/// Despawns all robots, tasks and charging stations, then respawns from `config`.
 fn reset_simulation(
     commands: &mut Commands,
-    meshes: &mut Assets<Mesh>,
-    materials: &mut Assets<StandardMaterial>,
     sim: &mut Simulation,
-    visuals: &RobotVisualMaterials,
     robot_entities: &[Entity],
     task_entities: &[Entity],
+    station_entities: &[Entity],
     config: &ScenarioConfig,
 ) {
-    for &e in robot_entities.iter().chain(task_entities) {
+    for &e in robot_entities
+        .iter()
+        .chain(task_entities)
+        .chain(station_entities)
+    {
         commands.entity(e).despawn();
     }
-    sim.now = 0.0;
-    sim.events.clear();
-    spawn_scenario(commands, meshes, materials, visuals, config);
+    sim.restart();
+    // Also replaces the `EntityIndex`, so no id maps to a despawned entity.
+    spawn_scenario(commands, config);
 }
 
 /// Switches to a new scenario when a scenario button is pressed.
 #[allow(clippy::too_many_arguments)]
 pub fn scenario_button_system(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    visuals: Res<RobotVisualMaterials>,
     mut active: ResMut<ActiveScenario>,
+    seed: Res<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
     mut sim: ResMut<Simulation>,
     mut button_query: Query<
         (&Interaction, &mut BackgroundColor, &ScenarioButton),
@@ -130,6 +279,7 @@ pub fn scenario_button_system(
     >,
     robot_entities: Query<Entity, With<Robot>>,
     task_entities: Query<Entity, With<Task>>,
+    station_entities: Query<Entity, With<ChargingStation>>,
 ) {
     let mut new_scenario = None;
 
@@ -153,27 +303,18 @@ pub fn scenario_button_system(
 
     let robots: Vec<Entity> = robot_entities.iter().collect();
     let tasks: Vec<Entity> = task_entities.iter().collect();
-    let config = ScenarioConfig::build(scenario);
-    reset_simulation(
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-        &mut sim,
-        &visuals,
-        &robots,
-        &tasks,
-        &config,
-    );
+    let stations: Vec<Entity> = station_entities.iter().collect();
+    let config = ScenarioConfig::build(scenario, seed.0, &library);
+    reset_simulation(&mut commands, &mut sim, &robots, &tasks, &stations, &config);
 }
 
 /// Restarts the current scenario when the restart button is pressed.
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn restart_button_system(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    visuals: Res<RobotVisualMaterials>,
     active: Res<ActiveScenario>,
+    seed: Res<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
     mut sim: ResMut<Simulation>,
     mut button_query: Query<
         (&Interaction, &mut BackgroundColor),
@@ -181,6 +322,7 @@ pub fn restart_button_system(
     >,
     robot_entities: Query<Entity, With<Robot>>,
     task_entities: Query<Entity, With<Task>>,
+    station_entities: Query<Entity, With<ChargingStation>>,
 ) {
     let mut should_restart = false;
 
@@ -199,19 +341,195 @@ pub fn restart_button_system(
         }
     }
 
-    if !should_restart { return; }
+    if !should_restart {
+        return;
+    }
 
     let robots: Vec<Entity> = robot_entities.iter().collect();
     let tasks: Vec<Entity> = task_entities.iter().collect();
-    let config = ScenarioConfig::build(active.0);
-    reset_simulation(
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-        &mut sim,
-        &visuals,
-        &robots,
-        &tasks,
-        &config,
-    );
+    let stations: Vec<Entity> = station_entities.iter().collect();
+    let config = ScenarioConfig::build(active.0, seed.0, &library);
+    reset_simulation(&mut commands, &mut sim, &robots, &tasks, &stations, &config);
+}
+
+/// Draws a new seed from the current one, or takes the seed typed in with the number keys
+/// once `Enter` is pressed, and restarts the scenario with it. `Backspace` deletes the last
+/// typed digit and `Escape` drops the entry.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn new_seed_button_system(
+    mut commands: Commands,
+    active: Res<ActiveScenario>,
+    mut seed: ResMut<SimulationSeed>,
+    library: Res<ScenarioLibrary>,
+    mut sim: ResMut<Simulation>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut entry: ResMut<SeedEntry>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<NewSeedButton>),
+    >,
+    robot_entities: Query<Entity, With<Robot>>,
+    task_entities: Query<Entity, With<Task>>,
+    station_entities: Query<Entity, With<ChargingStation>>,
+) {
+    let mut should_reseed = false;
+
+    for (interaction, mut color) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BackgroundColor(Color::srgb(0.10, 0.30, 0.60));
+                should_reseed = true;
+            }
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::srgb(0.24, 0.52, 0.95));
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::srgb(0.18, 0.45, 0.85));
+            }
+        }
+    }
+
+    for &(key, numpad, digit) in &DIGIT_KEYS {
+        if keyboard.any_just_pressed([key, numpad]) {
+            entry.0.push(digit);
+            // Digits past the largest seed are ignored.
+            if entry.0.parse::<u64>().is_err() {
+                entry.0.pop();
+            }
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        entry.0.pop();
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        entry.0.clear();
+    }
+    let typed = if keyboard.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+        std::mem::take(&mut entry.0).parse().ok()
+    } else {
+        None
+    };
+
+    if let Some(typed) = typed {
+        seed.0 = typed;
+    } else if should_reseed {
+        // Derive the next seed from the current one and keep it short enough to retype.
+        seed.0 = SimRng::new(seed.0).next_u64() % 1_000_000;
+    } else {
+        return;
+    }
+
+    let robots: Vec<Entity> = robot_entities.iter().collect();
+    let tasks: Vec<Entity> = task_entities.iter().collect();
+    let stations: Vec<Entity> = station_entities.iter().collect();
+    let config = ScenarioConfig::build(active.0, seed.0, &library);
+    reset_simulation(&mut commands, &mut sim, &robots, &tasks, &stations, &config);
+}
+
+/// Cycles the allocation strategy on click and keeps the button label in sync.
+///
+/// Switching applies to the next allocation round; robots already travelling keep their task.
+#[allow(clippy::type_complexity)]
+pub fn allocator_button_system(
+    mut allocator: ResMut<ActiveAllocator>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<AllocatorButton>),
+    >,
+    mut label_query: Query<&mut Text, With<AllocatorLabel>>,
+) {
+    for (interaction, mut color) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BackgroundColor(Color::srgb(0.15, 0.15, 0.15));
+                let next = allocator.kind.next();
+                allocator.select(next);
+            }
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::srgb(0.35, 0.35, 0.35));
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::srgb(0.25, 0.25, 0.25));
+            }
+        }
+    }
+
+    // Also picks up allocators selected by a scenario file.
+    if allocator.is_changed() {
+        for mut text in &mut label_query {
+            text.0 = format!("Allocator: {}", allocator.kind.label());
+        }
+    }
+}
+
+/// Applies clock actions from the pause/step/speed buttons.
+pub fn clock_button_system(
+    mut clock: ResMut<SimulationClock>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &ClockButton),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, mut color, action) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BackgroundColor(Color::srgb(0.15, 0.15, 0.15));
+                apply_clock_action(&mut clock, *action);
+            }
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::srgb(0.35, 0.35, 0.35));
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::srgb(0.25, 0.25, 0.25));
+            }
+        }
+    }
+}
+
+/// Keyboard shortcuts: `P` pause/resume, `N` step, `[` / `]` slower/faster.
+pub fn clock_keyboard_system(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    if keyboard.just_pressed(KeyCode::KeyP) {
+        apply_clock_action(&mut clock, ClockButton::TogglePause);
+    }
+    if keyboard.just_pressed(KeyCode::KeyN) {
+        apply_clock_action(&mut clock, ClockButton::Step);
+    }
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        apply_clock_action(&mut clock, ClockButton::Slower);
+    }
+    if keyboard.just_pressed(KeyCode::BracketRight) {
+        apply_clock_action(&mut clock, ClockButton::Faster);
+    }
+}
+
+fn apply_clock_action(clock: &mut SimulationClock, action: ClockButton) {
+    match action {
+        ClockButton::TogglePause => clock.toggle_pause(),
+        ClockButton::Step => clock.request_step(),
+        ClockButton::Slower => clock.slower(),
+        ClockButton::Faster => clock.faster(),
+    }
+}
+
+/// Refreshes the status label with clock state, sim time, and the active seed, or the seed
+/// being typed in.
+pub fn update_clock_status(
+    clock: Res<SimulationClock>,
+    sim: Res<Simulation>,
+    seed: Res<SimulationSeed>,
+    entry: Res<SeedEntry>,
+    mut text_query: Query<&mut Text, With<ClockStatusText>>,
+) {
+    let state = if clock.paused { "Paused" } else { "Running" };
+    let seed = if entry.0.is_empty() {
+        format!("seed {}", seed.0)
+    } else {
+        format!("seed {}_ (Enter to apply)", entry.0)
+    };
+    for mut text in &mut text_query {
+        text.0 = format!("{state} | {}x | t = {:.1}s | {seed}", clock.speed, sim.now);
+    }
 }